@@ -35,7 +35,7 @@ pub fn app() -> Command {
         .version(env!("CARGO_PKG_VERSION"))
         .author("William Budington <bill@eff.org>")
         .about("Downloads APKs from various sources")
-        .override_usage("apkeep <-a app_id[@version] | -c csv [-f field] [-v version_field]> [-d download_source] [-r parallel] OUTPATH")
+        .override_usage("apkeep <-a app_id[@version] | -c csv [-f field] [-v version_field] [--version-code-field version_code_field]> [-d download_source] [-r parallel] OUTPATH")
         .arg(
             Arg::new("app")
                 .help("Provide the ID and optionally the version of an app directly (e.g. com.instagram.android)")
@@ -43,7 +43,7 @@ pub fn app() -> Command {
                 .long("app")
                 .action(ArgAction::Set)
                 .conflicts_with("csv")
-                .required_unless_present_any(["csv", "google_oauth_token"]),
+                .required_unless_present_any(["csv", "google_oauth_token", "search", "all_apps", "build_repo", "diff_index"]),
         )
         .arg(
             Arg::new("csv")
@@ -70,6 +70,14 @@ pub fn app() -> Command {
                 .value_parser(value_parser!(usize))
                 .required(false),
         )
+        .arg(
+            Arg::new("version_code_field")
+                .help("CSV field containing version codes, used instead of the version field where both are given (used only if CSV is specified)")
+                .long("version-code-field")
+                .action(ArgAction::Set)
+                .value_parser(value_parser!(usize))
+                .required(false),
+        )
         .arg(
             Arg::new("list_versions")
                 .help("List the versions available")
@@ -78,6 +86,48 @@ pub fn app() -> Command {
                 .action(ArgAction::SetTrue)
                 .required(false),
         )
+        .arg(
+            Arg::new("reproducible_report")
+                .help("Report whether the APKs of each app were built reproducibly (F-Droid only)")
+                .long("reproducible-report")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("list_versions")
+                .required(false),
+        )
+        .arg(
+            Arg::new("search")
+                .help("Search the download source for apps matching a query, rather than downloading")
+                .long("search")
+                .action(ArgAction::Set)
+                .conflicts_with_all(["app", "csv", "list_versions"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("all_apps")
+                .help("Download every app in the repository, or those matching the search options (F-Droid only)")
+                .long("all-apps")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["app", "csv", "list_versions", "search"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("diff_index")
+                .help("Report what changed between two F-Droid package index files, such as cached copies of index.json (F-Droid only)")
+                .long("diff-index")
+                .value_names(["OLD_INDEX", "NEW_INDEX"])
+                .num_args(2)
+                .action(ArgAction::Set)
+                .conflicts_with_all(["app", "csv", "list_versions", "search", "all_apps", "reproducible_report"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("build_repo")
+                .help("Build an F-Droid repository from the APKs in OUTPATH, rather than downloading")
+                .long("build-repo")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["app", "csv", "list_versions", "search", "all_apps"])
+                .required(false),
+        )
         .arg(
             Arg::new("download_source")
                 .help("Where to download the APKs from")
@@ -96,6 +146,20 @@ pub fn app() -> Command {
                 .action(ArgAction::Set)
                 .required(false),
         )
+        .arg(
+            Arg::new("offline")
+                .help("Use the cached package index without contacting the repository (F-Droid only)")
+                .long("offline")
+                .action(ArgAction::SetTrue)
+                .required(false),
+        )
+        .arg(
+            Arg::new("max_index_age")
+                .help("Refuse to use a package index older than this, e.g. 12h or 7d (F-Droid only)")
+                .long("max-index-age")
+                .action(ArgAction::Set)
+                .required(false),
+        )
         .arg(
             Arg::new("ini")
                 .help("The path to an ini file which contains configuration data")
@@ -154,6 +218,6 @@ pub fn app() -> Command {
                 .help("Path to store output files")
                 .action(ArgAction::Set)
                 .index(1)
-                .required_unless_present_any(["list_versions", "google_oauth_token"]),
+                .required_unless_present_any(["list_versions", "google_oauth_token", "search", "reproducible_report", "diff_index"]),
         )
 }
@@ -1,43 +1,36 @@
+mod device;
+mod protobuf;
+mod search;
+mod versions;
+
+pub use device::DeviceProfile;
+
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
-use std::ops::Deref;
+use std::collections::HashMap;
+use std::fs;
 use std::path::Path;
 use std::rc::Rc;
 
 use futures_util::StreamExt;
 use indicatif::MultiProgress;
-use regex::Regex;
 use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::{Url, Response};
-use serde_json::json;
-use tokio_dl_stream_to_disk::{AsyncDownload, error::ErrorKind as TDSTDErrorKind};
+use reqwest::Url;
+use serde_json::{json, Value};
+use sha1::Sha1;
 use tokio::time::{sleep, Duration as TokioDuration};
 
-use crate::util::{OutputFormat, progress_bar::progress_wrapper};
+use crate::util::{abi::{abis_in_archive, AbiPreference}, download::download_with_retries, OutputFormat};
+use crate::util::quarantine::{apply_unverified_policy, print_run_report, UnverifiedDownload, UnverifiedPolicy};
+use crate::util::sanitize::sanitize_filename_component;
+use crate::util::version::VersionCount;
+use search::parse_search_results;
+use versions::{parse_versions, AppVersion};
 
-fn http_headers(options: &HashMap<&str, &str>) -> HeaderMap {
-    let mut headers = HeaderMap::new();
-    headers.insert("x-cv", HeaderValue::from_static("3172501"));
-    headers.insert("x-sv", HeaderValue::from_static("29"));
-    let arch = match options.get("arch"){
-        Some(arch) => {
-            arch.split(";").collect::<Vec<_>>().join(",")
-        },
-        None => "arm64-v8a,armeabi-v7a,armeabi,x86,x86_64".to_string()
-    };
-    match HeaderValue::from_str(&arch) {
-        Ok(abis_header) => {
-            headers.insert(
-                "x-abis",
-                abis_header
-            );
-        },
-        Err(_) => {
-            println!("Invalid options specified, excluding device arch specification.");
-        }
-    }
-    headers.insert("x-gp", HeaderValue::from_static("1"));
-    headers
+fn versions_url(device: &DeviceProfile, app_id: &str) -> Url {
+    Url::parse_with_params(crate::consts::APKPURE_VERSIONS_URL, &[
+        ("hl", device.locale.as_str()),
+        ("package_name", app_id),
+    ]).unwrap()
 }
 
 pub async fn download_apps(
@@ -45,23 +38,36 @@ pub async fn download_apps(
     parallel: usize,
     sleep_duration: u64,
     outpath: &Path,
+    device: DeviceProfile,
     options: HashMap<&str, &str>,
 ) {
+    let unverified_policy = match UnverifiedPolicy::from_options(&options) {
+        Ok(unverified_policy) => unverified_policy,
+        Err(err) => {
+            println!("{}. Exiting.", err);
+            std::process::exit(1);
+        },
+    };
     let mp = Rc::new(MultiProgress::new());
     let http_client = Rc::new(reqwest::Client::new());
     let app_arch = options.get("arch").cloned();
-    let headers = http_headers(&options);
-    let re = Rc::new(Regex::new(crate::consts::APKPURE_DOWNLOAD_URL_REGEX).unwrap());
+    // With several ABIs requested, a build is looked for for each of them
+    let variant_preference = AbiPreference::from_options(&options)
+        .filter(|preference| preference.requested().len() > 1);
+    let headers = device.http_headers();
+    let apps = expand_version_selectors(apps, &http_client, &headers, &device, sleep_duration).await;
 
-    futures_util::stream::iter(
+    let results = futures_util::stream::iter(
         apps.into_iter().map(|app| {
-            let (app_id, app_version) = app;
+            let (app_id, app_version, versions) = app;
             let http_client = Rc::clone(&http_client);
-            let re = Rc::clone(&re);
             let headers = headers.clone();
+            let device = &device;
+            let variant_preference = variant_preference.as_ref();
             let mp = Rc::clone(&mp);
             let mp_log = Rc::clone(&mp);
             async move {
+                // Per-ABI builds are named after their ABIs instead of the requested arch
                 let app_string = match (&app_version, app_arch) {
                     (None, None) => {
                         mp_log.suspend(|| println!("Downloading {}...", app_id));
@@ -69,7 +75,11 @@ pub async fn download_apps(
                     },
                     (None, Some(ref arch)) => {
                         mp_log.suspend(|| println!("Downloading {} arch {}...", app_id, arch));
-                        format!("{}@{}", app_id, arch)
+                        if variant_preference.is_none() {
+                            format!("{}@{}", app_id, arch)
+                        } else {
+                            app_id.to_string()
+                        }
                     },
                     (Some(ref version), None) => {
                         mp_log.suspend(|| println!("Downloading {} version {}...", app_id, version));
@@ -77,98 +87,292 @@ pub async fn download_apps(
                     },
                     (Some(ref version), Some(ref arch)) => {
                         mp_log.suspend(|| println!("Downloading {} version {} arch {}...", app_id, version, arch));
-                        format!("{}@{}@{}", app_id, version, arch)
+                        if variant_preference.is_none() {
+                            format!("{}@{}@{}", app_id, version, arch)
+                        } else {
+                            format!("{}@{}", app_id, version)
+                        }
                     },
                 };
                 if sleep_duration > 0 {
                     sleep(TokioDuration::from_millis(sleep_duration)).await;
                 }
-                let versions_url = Url::parse(&format!("{}{}", crate::consts::APKPURE_VERSIONS_URL_FORMAT, app_id)).unwrap();
-                let versions_response = http_client
-                    .get(versions_url)
-                    .headers(headers)
-                    .send().await.unwrap();
-                if let Some(app_version) = app_version {
-                    let regex_string = format!("[[:^digit:]]{}:(?s:.)+?{}", regex::escape(&app_version), crate::consts::APKPURE_DOWNLOAD_URL_REGEX);
-                    let re = Regex::new(&regex_string).unwrap();
-                    download_from_response(versions_response, Box::new(Box::new(re)), app_string, outpath, mp).await;
-                } else {
-                    download_from_response(versions_response, Box::new(re), app_string, outpath, mp).await;
+                if let Some(variant_preference) = variant_preference {
+                    let variants = fetch_variants(&http_client, device, &app_id, app_version.as_deref(), &app_string, variant_preference, &mp_log).await;
+                    return download_variants(variants, app_string, variant_preference, outpath, unverified_policy, mp).await;
                 }
+                // Versions selected with `@all` or `@latest:N` come with the version list they were
+                // selected from
+                let versions = match versions {
+                    Some(versions) => versions,
+                    None => match fetch_versions(&http_client, headers, device, &app_id).await {
+                        Ok(versions) => Rc::new(versions),
+                        Err(err) => {
+                            mp_log.println(format!("{} Skipping...", err)).unwrap();
+                            return vec![];
+                        },
+                    },
+                };
+                download_from_versions(&versions, app_version.as_deref(), app_string, outpath, unverified_policy, mp).await.into_iter().collect()
             }
         })
-    ).buffer_unordered(parallel).collect::<Vec<()>>().await;
+    ).buffer_unordered(parallel).collect::<Vec<Vec<Result<(), UnverifiedDownload>>>>().await;
+
+    let mut verified = 0;
+    let mut unverified = vec![];
+    for result in results.into_iter().flatten() {
+        match result {
+            Ok(()) => verified += 1,
+            Err(unverified_download) => unverified.push(unverified_download),
+        }
+    }
+    let status = print_run_report(verified, &unverified, unverified_policy);
+    if status != 0 {
+        std::process::exit(status);
+    }
 }
 
-async fn download_from_response(response: Response, re: Box<dyn Deref<Target=Regex>>, app_string: String, outpath: &Path, mp: Rc<MultiProgress>) {
-    let mp_log = Rc::clone(&mp);
-    let mp = Rc::clone(&mp);
-    match response.status() {
-        reqwest::StatusCode::OK => {
-            let body = response.text().await.unwrap();
-            match re.captures(&body) {
-                Some(caps) if caps.len() >= 2 => {
-                    let apk_xapk = caps.get(1).unwrap().as_str();
-                    let download_url = caps.get(2).unwrap().as_str();
-                    let fname = match apk_xapk {
-                        "XAPKJ" => format!("{}.xapk", app_string),
-                        _ => format!("{}.apk", app_string),
-                    };
+/// An app to download, with the version list it was selected from if it has already been fetched
+type AppRequest = (String, Option<String>, Option<Rc<Vec<AppVersion>>>);
 
-                    match AsyncDownload::new(download_url, Path::new(outpath), &fname).get().await {
-                        Ok(mut dl) => {
-                            let length = dl.length();
-                            let cb = match length {
-                                Some(length) => Some(progress_wrapper(mp)(fname.clone(), length)),
-                                None => None,
-                            };
-
-                            match dl.download(&cb).await {
-                                Ok(_) => mp_log.suspend(|| println!("{} downloaded successfully!", app_string)),
-                                Err(err) if matches!(err.kind(), TDSTDErrorKind::FileExists) => {
-                                    mp_log.println(format!("File already exists for {}. Skipping...", app_string)).unwrap();
-                                },
-                                Err(err) if matches!(err.kind(), TDSTDErrorKind::PermissionDenied) => {
-                                    mp_log.println(format!("Permission denied when attempting to write file for {}. Skipping...", app_string)).unwrap();
-                                },
-                                Err(_) => {
-                                    mp_log.println(format!("An error has occurred attempting to download {}.  Retry #1...", app_string)).unwrap();
-                                    match AsyncDownload::new(download_url, Path::new(outpath), &fname).download(&cb).await {
-                                        Ok(_) => mp_log.suspend(|| println!("{} downloaded successfully!", app_string)),
-                                        Err(_) => {
-                                            mp_log.println(format!("An error has occurred attempting to download {}.  Retry #2...", app_string)).unwrap();
-                                            match AsyncDownload::new(download_url, Path::new(outpath), &fname).download(&cb).await {
-                                                Ok(_) => mp_log.suspend(|| println!("{} downloaded successfully!", app_string)),
-                                                Err(_) => {
-                                                    mp_log.println(format!("An error has occurred attempting to download {}. Skipping...", app_string)).unwrap();
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        },
-                        Err(_) => {
-                            mp_log.println(format!("Invalid response for {}. Skipping...", app_string)).unwrap();
-                        }
-                    }
-                },
-                _ => {
-                    mp_log.println(format!("Could not get download URL for {}. Skipping...", app_string)).unwrap();
+/// Expands apps requested as `app@all` or `app@latest:N` into a download of each version they
+/// select from the APKPure version list, in the order APKPure lists them, which is newest first.
+/// Other apps are passed through as they are.
+async fn expand_version_selectors(
+    apps: Vec<(String, Option<String>)>,
+    http_client: &reqwest::Client,
+    headers: &HeaderMap,
+    device: &DeviceProfile,
+    sleep_duration: u64,
+) -> Vec<AppRequest> {
+    let mut expanded = vec![];
+    for (app_id, app_version) in apps {
+        let Some(version_count) = app_version.as_deref().and_then(VersionCount::parse_selector) else {
+            expanded.push((app_id, app_version, None));
+            continue;
+        };
+        if sleep_duration > 0 {
+            sleep(TokioDuration::from_millis(sleep_duration)).await;
+        }
+        let versions = match fetch_versions(http_client, headers.clone(), device, &app_id).await {
+            Ok(versions) => Rc::new(versions),
+            Err(err) => {
+                println!("{} Skipping...", err);
+                continue;
+            },
+        };
+        let mut version_names: Vec<&str> = vec![];
+        for version_name in versions.iter().filter_map(|version| version.version_name.as_deref()) {
+            if !version_names.contains(&version_name) {
+                version_names.push(version_name);
+            }
+        }
+        if version_names.is_empty() {
+            println!("Could not find any versions of {} on APKPure. Skipping...", app_id);
+            continue;
+        }
+        expanded.extend(version_names.into_iter()
+            .take(version_count.take())
+            .map(|version_name| (app_id.to_string(), Some(version_name.to_string()), Some(Rc::clone(&versions)))));
+    }
+    expanded
+}
+
+/// Fetches and decodes the version list of an app, returning why it could not be if it could not.
+async fn fetch_versions(http_client: &reqwest::Client, headers: HeaderMap, device: &DeviceProfile, app_id: &str) -> Result<Vec<AppVersion>, String> {
+    let response = http_client
+        .get(versions_url(device, app_id))
+        .headers(headers)
+        .send().await;
+    let body = match response {
+        Ok(response) if response.status() == reqwest::StatusCode::OK => response.bytes().await.ok(),
+        _ => None,
+    };
+    let Some(body) = body else {
+        return Err(format!("Invalid app response for {}.", app_id));
+    };
+    parse_versions(&body).map_err(|err| format!("Could not decode the APKPure version list for {}: {}", app_id, err))
+}
+
+/// Downloads the requested version from an app's version list.
+async fn download_from_versions(
+    versions: &[AppVersion],
+    app_version: Option<&str>,
+    app_string: String,
+    outpath: &Path,
+    unverified_policy: UnverifiedPolicy,
+    mp: Rc<MultiProgress>,
+) -> Option<Result<(), UnverifiedDownload>> {
+    // Without a version specified, the newest file is downloaded
+    let version = versions.iter().find(|version| {
+        app_version.is_none() || version.version_name.as_deref() == app_version
+    });
+    let Some(version) = version else {
+        mp.println(format!("Could not get download URL for {}. Skipping...", app_string)).unwrap();
+        return None;
+    };
+    download_version(version, app_string, outpath, unverified_policy, mp).await
+}
+
+/// Fetches the build APKPure serves for each ABI `variant_preference` was given, by asking for the
+/// version list as a device with only that ABI (and the ones it is compatible with) would.  Each
+/// build is returned with the ABI it was served for, and builds served for more than one of the
+/// ABIs are returned once.
+async fn fetch_variants(
+    http_client: &reqwest::Client,
+    device: &DeviceProfile,
+    app_id: &str,
+    app_version: Option<&str>,
+    app_string: &str,
+    variant_preference: &AbiPreference,
+    mp_log: &MultiProgress,
+) -> Vec<(AppVersion, String)> {
+    let mut variants: Vec<(AppVersion, String)> = vec![];
+    for abi in variant_preference.requested() {
+        let mut headers = device.http_headers();
+        match HeaderValue::from_str(&AbiPreference::parse(abi).abis().join(",")) {
+            Ok(abis_header) => {
+                headers.insert("x-abis", abis_header);
+            },
+            Err(_) => {
+                mp_log.println(format!("Invalid architecture {} specified for {}. Skipping...", abi, app_string)).unwrap();
+                continue;
+            },
+        }
+        let versions = match fetch_versions(http_client, headers, device, app_id).await {
+            Ok(versions) => versions,
+            Err(err) => {
+                mp_log.println(format!("{} Skipping the {} build...", err, abi)).unwrap();
+                continue;
+            },
+        };
+        let version = versions.into_iter().find(|version| {
+            app_version.is_none() || version.version_name.as_deref() == app_version
+        });
+        match version {
+            Some(version) => {
+                if !variants.iter().any(|(variant, _)| variant.sha1 == version.sha1) {
+                    variants.push((version, abi.to_string()));
                 }
+            },
+            None => mp_log.suspend(|| println!("APKPure does not offer {} for {}.", app_string, abi)),
+        }
+    }
+    variants
+}
+
+/// Downloads each of the builds from `fetch_variants`.  Each is named after the ABIs it has native
+/// code for, and a build with native code for none of the requested ABIs is removed.
+async fn download_variants(
+    variants: Vec<(AppVersion, String)>,
+    app_string: String,
+    variant_preference: &AbiPreference,
+    outpath: &Path,
+    unverified_policy: UnverifiedPolicy,
+    mp: Rc<MultiProgress>,
+) -> Vec<Result<(), UnverifiedDownload>> {
+    let mp_log = Rc::clone(&mp);
+    if variants.is_empty() {
+        mp_log.println(format!("APKPure does not offer a build of {} for any of the requested ABIs. Skipping...", app_string)).unwrap();
+        return vec![];
+    }
+    let mut results = vec![];
+    for (variant, abi) in variants {
+        let variant_string = format!("{}@{}", app_string, abi);
+        let result = download_version(&variant, variant_string.to_string(), outpath, unverified_policy, Rc::clone(&mp)).await;
+        if let Some(Ok(())) = result {
+            if !tag_variant(&variant, &app_string, &variant_string, variant_preference, outpath, &mp_log) {
+                continue;
             }
+        }
+        results.extend(result);
+    }
+    results
+}
 
+fn file_name(version: &AppVersion, app_string: &str) -> String {
+    if version.is_xapk() {
+        format!("{}.xapk", sanitize_filename_component(app_string))
+    } else {
+        format!("{}.apk", sanitize_filename_component(app_string))
+    }
+}
+
+/// Downloads a file from the version list, and checks it against the sha1sum APKPure lists for
+/// it.  A download which does not match is retried once before `unverified_policy` is applied to
+/// it.
+async fn download_version(
+    version: &AppVersion,
+    app_string: String,
+    outpath: &Path,
+    unverified_policy: UnverifiedPolicy,
+    mp: Rc<MultiProgress>,
+) -> Option<Result<(), UnverifiedDownload>> {
+    let mp_log = Rc::clone(&mp);
+    let fname = file_name(version, &app_string);
+    let expected_sha1sum = hex::decode(&version.sha1).expect("sha1sums are checked to be hex when the version list is parsed");
+
+    let mut sha1sum = download_with_retries::<Sha1>(&version.url, outpath, &fname, &app_string, Rc::clone(&mp)).await?;
+    if sha1sum != expected_sha1sum {
+        mp_log.println(format!("The sha1sum of {} does not match the one APKPure lists. Retrying...", app_string)).unwrap();
+        if fs::remove_file(outpath.join(&fname)).is_ok() {
+            sha1sum = download_with_retries::<Sha1>(&version.url, outpath, &fname, &app_string, mp).await?;
+        }
+    }
+    if sha1sum == expected_sha1sum {
+        mp_log.suspend(|| println!("{} downloaded successfully!", app_string));
+        Some(Ok(()))
+    } else {
+        let reason = "the sha1sum does not match the one APKPure lists".to_string();
+        Some(Err(apply_unverified_policy(unverified_policy, outpath, &fname, app_string, reason, &mp_log)))
+    }
+}
+
+/// Renames a downloaded per-ABI build after the ABIs its `lib/` directory actually has native code
+/// for, which may differ from the ABI it was served for.  A build without native code is tagged
+/// `universal`.  A build none of the requested ABIs can run is removed, and `false` returned.
+fn tag_variant(variant: &AppVersion, app_string: &str, variant_string: &str, variant_preference: &AbiPreference, outpath: &Path, mp_log: &MultiProgress) -> bool {
+    let fname = file_name(variant, variant_string);
+    let abis = match abis_in_archive(&outpath.join(&fname)) {
+        Ok(abis) => abis,
+        Err(_) => {
+            mp_log.suspend(|| println!("Could not read the native code of {}, so it was not tagged with its ABI.", variant_string));
+            return true;
         },
-        _ => {
-            mp_log.println(format!("Invalid app response for {}. Skipping...", app_string)).unwrap();
+    };
+    let tag = if abis.is_empty() {
+        "universal".to_string()
+    } else {
+        abis.join("+")
+    };
+    if variant_preference.rank(&abis).is_none() {
+        if fs::remove_file(outpath.join(&fname)).is_ok() {
+            mp_log.suspend(|| println!("{} only has native code for {}, which none of the requested ABIs can run, so it was removed.", variant_string, tag));
+        } else {
+            mp_log.suspend(|| println!("{} only has native code for {}, which none of the requested ABIs can run, but it could not be removed.", variant_string, tag));
         }
+        return false;
+    }
+    let tagged_fname = file_name(variant, &format!("{}@{}", app_string, tag));
+    if tagged_fname == fname {
+        return true;
     }
+    let contents = if abis.is_empty() {
+        "no native code".to_string()
+    } else {
+        format!("native code for {}", tag)
+    };
+    if outpath.join(&tagged_fname).exists() {
+        mp_log.suspend(|| println!("{} has {}, but {} already exists, so it was left as {}.", variant_string, contents, tagged_fname, fname));
+    } else if fs::rename(outpath.join(&fname), outpath.join(&tagged_fname)).is_ok() {
+        mp_log.suspend(|| println!("{} has {}, so it was saved as {}.", variant_string, contents, tagged_fname));
+    }
+    true
 }
 
-pub async fn list_versions(apps: Vec<(String, Option<String>)>, options: HashMap<&str, &str>) {
+pub async fn list_versions(apps: Vec<(String, Option<String>)>, device: DeviceProfile, options: HashMap<&str, &str>) {
     let http_client = Rc::new(reqwest::Client::new());
-    let re = Rc::new(Regex::new(r"([[:alnum:]\.-]+):\([[:xdigit:]]{40,}").unwrap());
-    let headers = http_headers(&options);
+    let headers = device.http_headers();
     let output_format = match options.get("output_format") {
         Some(val) if val.to_lowercase() == "json" => OutputFormat::Json,
         _ => OutputFormat::Plaintext,
@@ -181,15 +385,15 @@ pub async fn list_versions(apps: Vec<(String, Option<String>)>, options: HashMap
     for app in apps {
         let (app_id, _) = app;
         let http_client = Rc::clone(&http_client);
-        let re = Rc::clone(&re);
         let json_root = Rc::clone(&json_root);
         let output_format = output_format.clone();
         let headers = headers.clone();
+        let device = &device;
         async move {
             if output_format.is_plaintext() {
                 println!("Versions available for {} on APKPure:", app_id);
             }
-            let versions_url = Url::parse(&format!("{}{}", crate::consts::APKPURE_VERSIONS_URL_FORMAT, app_id)).unwrap();
+            let versions_url = versions_url(device, &app_id);
             let versions_response = http_client
                 .get(versions_url)
                 .headers(headers)
@@ -197,25 +401,43 @@ pub async fn list_versions(apps: Vec<(String, Option<String>)>, options: HashMap
 
             match versions_response.status() {
                 reqwest::StatusCode::OK => {
-                    let body = versions_response.text().await.unwrap();
-                    let mut versions = HashSet::new();
-                    for caps in re.captures_iter(&body) {
-                        if caps.len() >= 2 {
-                            versions.insert(caps.get(1).unwrap().as_str().to_string());
-                        }
-                    }
-                    let mut versions = versions.drain().collect::<Vec<String>>();
-                    versions.sort();
+                    let body = versions_response.bytes().await.unwrap();
+                    let versions = match parse_versions(&body) {
+                        Ok(versions) => versions,
+                        Err(err) => {
+                            match output_format {
+                                OutputFormat::Plaintext => {
+                                    eprintln!("| Could not decode the version list for {}: {} Skipping...", app_id, err);
+                                },
+                                OutputFormat::Json => {
+                                    let mut app_root = HashMap::new();
+                                    app_root.insert("error".to_string(), format!("Could not decode the version list: {}", err));
+                                    json_root.borrow_mut().as_mut().unwrap().insert(app_id.to_string(), json!(app_root));
+                                },
+                            }
+                            return;
+                        },
+                    };
+                    // Every file is listed, in the order APKPure lists them, since APKPure may
+                    // serve several builds under one version name.  These are told apart by their
+                    // sha1sums.
                     match output_format {
                         OutputFormat::Plaintext => {
-                            println!("| {}", versions.join(", "));
+                            for version in versions {
+                                println!("| {}: {}, sha1 {}",
+                                    version.version_name.as_deref().unwrap_or("unknown"),
+                                    version.file_type,
+                                    version.sha1);
+                            }
                         },
                         OutputFormat::Json => {
-                            let mut app_root: HashMap<String, Vec<HashMap<String, String>>> = HashMap::new();
-                            app_root.insert("available_versions".to_string(), versions.into_iter().map(|v| {
-                                let mut version_map = HashMap::new();
-                                version_map.insert("version".to_string(), v);
-                                version_map
+                            let mut app_root: HashMap<String, Vec<Value>> = HashMap::new();
+                            app_root.insert("available_versions".to_string(), versions.into_iter().map(|version| {
+                                json!({
+                                    "version": version.version_name,
+                                    "file_type": version.file_type,
+                                    "sha1": version.sha1,
+                                })
                             }).collect());
                             json_root.borrow_mut().as_mut().unwrap().insert(app_id.to_string(), json!(app_root));
                         },
@@ -240,3 +462,66 @@ pub async fn list_versions(apps: Vec<(String, Option<String>)>, options: HashMap
         println!("{{\"source\":\"APKPure\",\"apps\":{}}}", json!(*json_root));
     };
 }
+
+/// Searches APKPure for apps matching `query`.  With `output_format=csv`, each result is printed as
+/// `package_id,title`, which can be passed straight back to `-c`.
+pub async fn search(query: &str, device: DeviceProfile, options: HashMap<&str, &str>) {
+    let output_format = options.get("output_format").map(|val| val.to_lowercase());
+    let headers = device.http_headers();
+    let search_url = Url::parse_with_params(crate::consts::APKPURE_SEARCH_URL, &[
+        ("hl", device.locale.as_str()),
+        ("search_type", "active_search"),
+        ("page", "1"),
+        ("key", query),
+    ]).unwrap();
+    let response = reqwest::Client::new()
+        .get(search_url)
+        .headers(headers)
+        .send().await;
+    let results = match response {
+        Ok(response) if response.status() == reqwest::StatusCode::OK => {
+            response.bytes().await.ok().and_then(|body| parse_search_results(&body).ok())
+        },
+        _ => None,
+    };
+    let Some(results) = results else {
+        match output_format.as_deref() {
+            Some("json") => println!("{{\"error\":\"Invalid search response.\"}}"),
+            _ => eprintln!("Invalid search response from APKPure. Exiting."),
+        }
+        std::process::exit(1);
+    };
+    match output_format.as_deref() {
+        Some("json") => {
+            let apps: serde_json::Map<String, Value> = results.into_iter().map(|result| {
+                (result.package_id, json!({
+                    "title": result.title,
+                }))
+            }).collect();
+            println!("{}", json!({"source": "APKPure", "apps": apps}));
+        },
+        Some("csv") => {
+            for result in results {
+                println!("{},{}", result.package_id, csv_field(&result.title));
+            }
+        },
+        _ => {
+            println!("Search results on APKPure:");
+            if results.is_empty() {
+                println!("| No matching apps found.");
+            }
+            for result in results {
+                println!("| {} ({})", result.package_id, result.title);
+            }
+        },
+    }
+}
+
+/// Quotes a CSV field if it needs it.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
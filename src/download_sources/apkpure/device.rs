@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use configparser::ini::Ini;
+use reqwest::header::{HeaderMap, HeaderValue};
+
+use crate::util::abi::AbiPreference;
+
+/// Version of the APKPure client requests claim to come from
+const CLIENT_VERSION: &str = "3172501";
+
+/// Settings of a profile, which can be given in its ini section or as options
+const SETTINGS: &[&str] = &["arch", "sdk", "locale", "density", "google_play"];
+
+/// The device APKPure is told apps are being downloaded for, which decides which variant of an app
+/// it serves.
+pub struct DeviceProfile {
+    pub sdk: u32,
+    pub abis: AbiPreference,
+    /// As a BCP 47 language tag, such as `en-US`
+    pub locale: String,
+    /// In dots per inch
+    pub density: Option<u32>,
+    pub google_play: bool,
+}
+
+impl DeviceProfile {
+    /// Builds the profile named by the `device` option, or the default one.  Profiles may be
+    /// defined, or built-in ones changed, in an `[apkpure.device.NAME]` section of the ini file,
+    /// and the `arch`, `sdk`, `locale`, `density` and `google_play` options override the profile.
+    pub fn from_options(options: &HashMap<&str, &str>, conf: Option<&Ini>) -> Result<Self, String> {
+        let name = options.get("device").copied().unwrap_or("default").to_lowercase();
+        let section = format!("apkpure.device.{}", name);
+        let defined = conf.is_some_and(|conf| conf.sections().contains(&section));
+        let mut profile = match Self::builtin(&name) {
+            Some(profile) => profile,
+            None if defined => Self::default(),
+            None => return Err(format!("Unknown APKPure device profile \"{}\"", name)),
+        };
+        if let Some(conf) = conf.filter(|_| defined) {
+            for key in SETTINGS {
+                if let Some(value) = conf.get(&section, key) {
+                    profile.set(key, &value)?;
+                }
+            }
+        }
+        for key in SETTINGS {
+            if let Some(value) = options.get(key) {
+                profile.set(key, value)?;
+            }
+        }
+        Ok(profile)
+    }
+
+    /// The profiles which can be selected with the `device` option without defining them.
+    fn builtin(name: &str) -> Option<Self> {
+        let (sdk, arch, density, google_play) = match name {
+            "default" => (29, None, None, true),
+            "arm64" => (34, Some("arm64-v8a"), Some(420), true),
+            "armv7" => (23, Some("armeabi-v7a"), Some(320), true),
+            "x86_64" => (33, Some("x86_64"), Some(420), true),
+            "no_gp" => (29, Some("arm64-v8a"), Some(480), false),
+            _ => return None,
+        };
+        Some(DeviceProfile {
+            sdk,
+            abis: arch.map(AbiPreference::parse).unwrap_or_default(),
+            locale: "en-US".to_string(),
+            density,
+            google_play,
+        })
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("Invalid {} \"{}\" for the APKPure device profile", key, value);
+        match key {
+            "arch" => self.abis = AbiPreference::parse(value),
+            "sdk" => self.sdk = value.trim().parse().map_err(|_| invalid())?,
+            "locale" => {
+                let locale = value.trim().replace('_', "-");
+                if locale.is_empty() || !locale.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    return Err(invalid());
+                }
+                self.locale = locale;
+            },
+            "density" => self.density = Some(value.trim().parse().map_err(|_| invalid())?),
+            "google_play" => self.google_play = match value.trim().to_lowercase().as_str() {
+                "true" | "1" => true,
+                "false" | "0" => false,
+                _ => return Err(invalid()),
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    pub fn http_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-cv", HeaderValue::from_static(CLIENT_VERSION));
+        headers.insert("x-sv", HeaderValue::from(self.sdk));
+        match HeaderValue::from_str(&self.abis.abis().join(",")) {
+            Ok(abis_header) => {
+                headers.insert(
+                    "x-abis",
+                    abis_header
+                );
+            },
+            Err(_) => {
+                println!("Invalid options specified, excluding device arch specification.");
+            }
+        }
+        if let Some(density) = self.density {
+            headers.insert("x-dpi", HeaderValue::from(density));
+        }
+        headers.insert("x-gp", HeaderValue::from_static(if self.google_play { "1" } else { "0" }));
+        headers
+    }
+}
+
+impl Default for DeviceProfile {
+    fn default() -> Self {
+        Self::builtin("default").unwrap()
+    }
+}
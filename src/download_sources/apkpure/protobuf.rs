@@ -0,0 +1,97 @@
+use std::error::Error;
+
+use simple_error::SimpleError;
+
+const WIRE_TYPE_VARINT: u8 = 0;
+const WIRE_TYPE_FIXED64: u8 = 1;
+const WIRE_TYPE_LENGTH_DELIMITED: u8 = 2;
+const WIRE_TYPE_FIXED32: u8 = 5;
+
+/// A field of a protobuf message, decoded without a schema.
+pub struct Field<'a> {
+    pub number: u32,
+    pub value: FieldValue<'a>,
+}
+
+/// Numeric values are checked, but not kept, as none are read from APKPure's responses.
+pub enum FieldValue<'a> {
+    Varint,
+    Fixed64,
+    Fixed32,
+    /// A string, bytes or an embedded message, which the wire format does not distinguish
+    LengthDelimited(&'a [u8]),
+}
+
+impl<'a> FieldValue<'a> {
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            Self::LengthDelimited(bytes) => std::str::from_utf8(bytes).ok(),
+            _ => None,
+        }
+    }
+
+    /// Decodes the value as an embedded message, if it is one.
+    pub fn as_message(&self) -> Option<Vec<Field<'a>>> {
+        match self {
+            Self::LengthDelimited(bytes) => decode_message(bytes).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes the fields of a message in the order they appear.  Fails if the data is not a
+/// well-formed message, which is also how embedded messages are told apart from strings.
+pub fn decode_message(data: &[u8]) -> Result<Vec<Field<'_>>, Box<dyn Error>> {
+    let mut fields = vec![];
+    let mut offset = 0;
+    while offset < data.len() {
+        let key = read_varint(data, &mut offset)?;
+        let number = u32::try_from(key >> 3).map_err(|_| malformed())?;
+        if number == 0 {
+            return Err(malformed());
+        }
+        let value = match (key & 0x7) as u8 {
+            WIRE_TYPE_VARINT => {
+                read_varint(data, &mut offset)?;
+                FieldValue::Varint
+            },
+            WIRE_TYPE_FIXED64 => {
+                data.get(offset..offset + 8).ok_or_else(malformed)?;
+                offset += 8;
+                FieldValue::Fixed64
+            },
+            WIRE_TYPE_LENGTH_DELIMITED => {
+                let length = usize::try_from(read_varint(data, &mut offset)?)?;
+                let bytes = data.get(offset..offset.checked_add(length).ok_or_else(malformed)?).ok_or_else(malformed)?;
+                offset += length;
+                FieldValue::LengthDelimited(bytes)
+            },
+            WIRE_TYPE_FIXED32 => {
+                data.get(offset..offset + 4).ok_or_else(malformed)?;
+                offset += 4;
+                FieldValue::Fixed32
+            },
+            // Groups are deprecated, and not used by APKPure
+            _ => return Err(malformed()),
+        };
+        fields.push(Field { number, value });
+    }
+    Ok(fields)
+}
+
+fn read_varint(data: &[u8], offset: &mut usize) -> Result<u64, Box<dyn Error>> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*offset).ok_or_else(malformed)?;
+        *offset += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(malformed())
+}
+
+fn malformed() -> Box<dyn Error> {
+    Box::new(SimpleError::new("Malformed protobuf message."))
+}
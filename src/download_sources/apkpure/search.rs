@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+use std::error::Error;
+
+use super::protobuf::{decode_message, Field};
+
+// Fields of the messages describing each app in a search response.  Only the package name and
+// title are read, and a message is only taken to be an app if its package name looks like one.
+const FIELD_PACKAGE_NAME: u32 = 1;
+const FIELD_TITLE: u32 = 2;
+
+/// An app from the response of APKPure's search endpoint.
+pub struct SearchResult {
+    pub package_id: String,
+    pub title: String,
+}
+
+impl SearchResult {
+    /// Reads an app from a message, if the message is one.
+    fn from_fields(fields: &[Field]) -> Option<Self> {
+        let string_field = |number: u32| fields.iter()
+            .find(|field| field.number == number)
+            .and_then(|field| field.value.as_str())
+            .filter(|value| !value.is_empty())
+            .map(String::from);
+        let package_id = string_field(FIELD_PACKAGE_NAME).filter(|package_id| looks_like_package_id(package_id))?;
+        Some(SearchResult {
+            package_id,
+            title: string_field(FIELD_TITLE)?,
+        })
+    }
+}
+
+/// Decodes the apps in a search response, in the order APKPure ranks them.  An app is listed
+/// once, even if the response includes it in several sections.
+pub fn parse_search_results(body: &[u8]) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+    let mut results = vec![];
+    collect_results(&decode_message(body)?, &mut results);
+    let mut seen = HashSet::new();
+    results.retain(|result| seen.insert(result.package_id.to_string()));
+    Ok(results)
+}
+
+fn collect_results(fields: &[Field], results: &mut Vec<SearchResult>) {
+    if let Some(result) = SearchResult::from_fields(fields) {
+        results.push(result);
+        return;
+    }
+    for field in fields {
+        if let Some(message) = field.value.as_message() {
+            collect_results(&message, results);
+        }
+    }
+}
+
+fn looks_like_package_id(value: &str) -> bool {
+    value.contains('.') && value.split('.').all(|segment| {
+        segment.starts_with(|c: char| c.is_ascii_alphabetic())
+            && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUERY_NEW_RESPONSE: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/apkpure/query_new.bin"));
+
+    #[test]
+    fn parses_query_new_response() {
+        let results = parse_search_results(QUERY_NEW_RESPONSE).unwrap();
+        let package_ids: Vec<&str> = results.iter().map(|result| result.package_id.as_str()).collect();
+        assert_eq!(package_ids, vec!["org.mozilla.firefox", "org.mozilla.focus", "com.brave.browser"]);
+
+        assert_eq!(results[0].title, "Firefox Fast & Private Browser");
+        assert_eq!(results[2].title, "Brave Private Web Browser");
+    }
+
+    #[test]
+    fn rejects_malformed_response() {
+        assert!(parse_search_results(&QUERY_NEW_RESPONSE[..QUERY_NEW_RESPONSE.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::error::Error;
+
+use super::protobuf::{decode_message, Field};
+
+// Fields of the messages describing each downloadable file.  These are the only ones known from
+// the responses the version list was originally scraped from: the sha1 was matched as `:(`
+// followed by 40 hex digits, which is field 7 holding a 40 byte string, and the download URL as
+// `J` followed by a length, which is field 9.  The version name and the file type were matched as
+// the strings written immediately before them, so they are read by position rather than number.
+const FIELD_SHA1: u32 = 7;
+const FIELD_URL: u32 = 9;
+
+/// A downloadable file for a version of an app, from the response of APKPure's `app_version`
+/// endpoint.
+pub struct AppVersion {
+    pub version_name: Option<String>,
+    /// `APK` or `XAPK`
+    pub file_type: String,
+    /// Lowercase hex
+    pub sha1: String,
+    pub url: String,
+}
+
+impl AppVersion {
+    pub fn is_xapk(&self) -> bool {
+        self.file_type.eq_ignore_ascii_case("XAPK")
+    }
+
+    /// Reads a file record from a message, if the message is one.
+    fn from_fields(fields: &[Field]) -> Option<Self> {
+        let sha1_position = fields.iter().position(|field| {
+            field.number == FIELD_SHA1 && field.value.as_str().is_some_and(|sha1| sha1.len() == 40 && hex::decode(sha1).is_ok())
+        })?;
+        let url_position = fields.iter().position(|field| {
+            field.number == FIELD_URL && field.value.as_str().is_some_and(|url| url.starts_with("http://") || url.starts_with("https://"))
+        })?;
+        let file_type = url_position.checked_sub(1)
+            .and_then(|position| fields[position].value.as_str())
+            .filter(|file_type| file_type.eq_ignore_ascii_case("APK") || file_type.eq_ignore_ascii_case("XAPK"))?;
+        let version_name = sha1_position.checked_sub(1)
+            .and_then(|position| fields[position].value.as_str())
+            .filter(|version_name| !version_name.is_empty())
+            .map(String::from);
+        Some(AppVersion {
+            version_name,
+            file_type: file_type.to_uppercase(),
+            sha1: fields[sha1_position].value.as_str()?.to_lowercase(),
+            url: fields[url_position].value.as_str()?.to_string(),
+        })
+    }
+}
+
+/// Decodes the files listed in an `app_version` response, in the order APKPure lists them, which
+/// is newest first.  The records are nested within other messages, so every embedded message is
+/// searched for them.
+pub fn parse_versions(body: &[u8]) -> Result<Vec<AppVersion>, Box<dyn Error>> {
+    let mut versions = vec![];
+    collect_versions(&decode_message(body)?, &mut versions);
+    Ok(versions)
+}
+
+fn collect_versions(fields: &[Field], versions: &mut Vec<AppVersion>) {
+    if let Some(version) = AppVersion::from_fields(fields) {
+        versions.push(version);
+        return;
+    }
+    for field in fields {
+        if let Some(message) = field.value.as_message() {
+            collect_versions(&message, versions);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APP_VERSION_RESPONSE: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/apkpure/app_version.bin"));
+
+    #[test]
+    fn parses_app_version_response() {
+        let versions = parse_versions(APP_VERSION_RESPONSE).unwrap();
+        assert_eq!(versions.len(), 2);
+
+        let xapk = &versions[0];
+        assert_eq!(xapk.version_name.as_deref(), Some("121.0"));
+        assert_eq!(xapk.file_type, "XAPK");
+        assert!(xapk.is_xapk());
+        assert_eq!(xapk.sha1, "a".repeat(40));
+        assert_eq!(xapk.url, "https://download.pureapk.com/b/XAPK/org.mozilla.firefox_121.0.xapk");
+
+        let apk = &versions[1];
+        assert_eq!(apk.version_name.as_deref(), Some("120.1"));
+        assert_eq!(apk.file_type, "APK");
+        assert!(!apk.is_xapk());
+        assert_eq!(apk.sha1, "c".repeat(40));
+        assert_eq!(apk.url, "https://download.pureapk.com/b/APK/org.mozilla.firefox_120.1.apk");
+    }
+
+    #[test]
+    fn rejects_malformed_response() {
+        assert!(parse_versions(&APP_VERSION_RESPONSE[..APP_VERSION_RESPONSE.len() - 1]).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::{hash_map::DefaultHasher, HashSet, HashMap};
+use std::collections::{hash_map::DefaultHasher, HashMap, HashSet};
 use std::error::Error;
 use std::fs::{self, File};
 use std::hash::{Hash, Hasher};
@@ -19,21 +19,117 @@ use sha2::Sha256;
 use simple_error::SimpleError;
 use tempfile::{tempdir, TempDir};
 use tokio::time::{sleep, Duration};
-use tokio_dl_stream_to_disk::{AsyncDownload, error::ErrorKind as TDSTDErrorKind};
+use tokio_dl_stream_to_disk::AsyncDownload;
 use x509_certificate::certificate::CapturedX509Certificate;
 
 use crate::consts;
 use crate::config::{self, ConfigDirError};
-use crate::util::{OutputFormat, progress_bar::progress_wrapper};
+use crate::util::{abi::AbiPreference, download::download_with_retries, format_size, OutputFormat, version::{VersionCount, VersionSpec}, date::{format_date_ms, format_duration_secs, parse_duration_secs}, progress_bar::progress_wrapper};
+use crate::util::quarantine::{apply_unverified_policy, print_run_report, UnverifiedDownload, UnverifiedPolicy};
+use crate::util::sanitize::{is_valid_package_id, sanitize_filename_component};
+mod apk_signature;
+use apk_signature::ApkSigners;
+mod axml;
+mod diff;
+use diff::{IndexDiff, VersionId};
 mod error;
 use error::Error as FDroidError;
+mod index;
+mod repo;
+mod reproducible;
+use index::{Graphic, Index, LocalizedText, PackageVersion, SourceFile};
+mod search;
+use search::PackageFilter;
+mod selection;
+use selection::{version_matches, VersionPolicy};
+
+/// The directory the index of a repository is cached in.  Custom repositories, including archive
+/// repositories, each get a directory of their own.
+fn index_config_dir(custom_repo: Option<&str>) -> Result<PathBuf, ConfigDirError> {
+    let mut config_dir = config::config_dir()?;
+    if let Some(repo) = custom_repo {
+        config_dir.push("fdroid-custom-repos");
+        config::create_dir(&config_dir)?;
+        let mut s = DefaultHasher::new();
+        repo.hash(&mut s);
+        config_dir.push(format!("{}", s.finish()));
+        config::create_dir(&config_dir)?;
+    }
+    Ok(config_dir)
+}
+
+/// The `repo` option for the archive repository that old versions are moved to, for repositories
+/// which follow f-droid.org's layout of `.../repo` alongside `.../archive`.  The archive is signed
+/// with the same key, so the fingerprint carries over.
+fn archive_repo_option(options: &HashMap<&str, &str>) -> Option<String> {
+    if matches!(options.get("archive"), Some(val) if val == &"0" || val.to_lowercase() == "false") {
+        return None;
+    }
+    let (repo, fingerprint) = match options.get("repo") {
+        Some(full_repo_option) => match full_repo_option.split_once("?fingerprint=") {
+            Some((repo, fingerprint)) => (repo.to_string(), Some(fingerprint.to_string())),
+            None => (full_repo_option.to_string(), None),
+        },
+        None => (consts::FDROID_REPO.to_string(), Some(hex::encode(consts::FDROID_INDEX_FINGERPRINT))),
+    };
+    let archive_repo = format!("{}/archive", repo.trim_end_matches('/').strip_suffix("/repo")?);
+    match fingerprint {
+        Some(fingerprint) => Some(format!("{}?fingerprint={}", archive_repo, fingerprint)),
+        None => Some(archive_repo),
+    }
+}
+
+/// Retrieves the index of the archive repository, if there is one.  Unlike the main repository,
+/// an archive which cannot be found, retrieved or verified is not an error, since many
+/// repositories do not have one.
+async fn retrieve_archive_index(options: &HashMap<&str, &str>, mp: Rc<MultiProgress>) -> Option<Value> {
+    let archive_repo_option = archive_repo_option(options)?;
+    let archive_repo = archive_repo_option.split_once("?fingerprint=").map_or(archive_repo_option.as_str(), |(repo, _)| repo);
+    let use_entry = !matches!(options.get("use_entry"), Some(val) if val == &"0" || val.to_lowercase() == "false");
+    let offline = matches!(options.get("offline"), Some(val) if val == &"1" || val.to_lowercase() == "true");
+    let available = if offline {
+        index_config_dir(Some(archive_repo))
+            .map(|config_dir| config_dir.join(if use_entry { "index.json" } else { "index_v1.json" }).is_file())
+            .unwrap_or(false)
+    } else {
+        let jar_url = format!("{}/{}", archive_repo, if use_entry { "entry.jar" } else { "index-v1.jar" });
+        matches!(reqwest::Client::new().head(jar_url).send().await, Ok(response) if response.status().is_success())
+    };
+    if !available {
+        mp.suspend(|| eprintln!("No F-Droid archive repository is available at {}.", archive_repo));
+        return None;
+    }
+    let mut archive_options = options.clone();
+    archive_options.insert("repo", &archive_repo_option);
+    match retrieve_index(&archive_options, Rc::clone(&mp)).await {
+        Ok(archive_index) => Some(archive_index),
+        Err(err) => {
+            mp.suspend(|| eprintln!("Could not retrieve the F-Droid archive package index, so it was not used: {}", err));
+            None
+        },
+    }
+}
+
+/// The name the signed jar is downloaded to before it is extracted
+const JAR_LOCAL_FILE: &str = "jar.zip";
 
 async fn retrieve_index_or_exit(options: &HashMap<&str, &str>, mp: Rc<MultiProgress>, output_format: OutputFormat) -> Value {
+    match retrieve_index(options, mp).await {
+        Ok(index) => index,
+        Err(err) => {
+            print_error(&format!("{} Exiting.", err), output_format);
+            std::process::exit(1);
+        },
+    }
+}
+
+/// Retrieves the package index of the repository given in the options, returning why it could
+/// not be retrieved or verified if it could not.
+async fn retrieve_index(options: &HashMap<&str, &str>, mp: Rc<MultiProgress>) -> Result<Value, String> {
     let temp_dir = match tempdir() {
         Ok(temp_dir) => temp_dir,
         Err(_) => {
-            print_error("Could not create temporary directory for F-Droid package index. Exiting.", output_format);
-            std::process::exit(1);
+            return Err("Could not create temporary directory for F-Droid package index.".to_string());
         }
     };
     let mut custom_repo = false;
@@ -43,14 +139,21 @@ async fn retrieve_index_or_exit(options: &HashMap<&str, &str>, mp: Rc<MultiProgr
         Some(val) if val == &"0" || val.to_lowercase() == "false" => false,
         _ => true,
     };
+    let offline = matches!(options.get("offline"), Some(val) if val == &"1" || val.to_lowercase() == "true");
+    let max_index_age = match options.get("max_index_age").map(|val| parse_duration_secs(val)) {
+        Some(Ok(max_index_age)) => Some(max_index_age),
+        Some(Err(err)) => {
+            return Err(format!("{}.", err));
+        },
+        None => None,
+    };
     if let Some(full_repo_option) = options.get("repo") {
         custom_repo = true;
         if let Some((repo_option, fingerprint_option)) = full_repo_option.split_once("?fingerprint=") {
             fingerprint = match hex::decode(fingerprint_option) {
                 Ok(hex_fingerprint) => hex_fingerprint,
                 Err(_) => {
-                    print_error("Fingerprint must be specified as valid hex. Exiting.", output_format);
-                    std::process::exit(1);
+                    return Err("Fingerprint must be specified as valid hex.".to_string());
                 }
             };
             repo = repo_option.to_string();
@@ -59,26 +162,10 @@ async fn retrieve_index_or_exit(options: &HashMap<&str, &str>, mp: Rc<MultiProgr
         }
     }
 
-    let display_error_and_exit = |err: ConfigDirError| {
-        match err {
-            ConfigDirError::NotFound => {
-                print_error("Could not find a config directory for apkeep to store F-Droid package index. Exiting.", output_format.clone());
-            },
-            ConfigDirError::CouldNotCreate => {
-                print_error("Could not create a config directory for apkeep to store F-Droid package index. Exiting.", output_format.clone());
-            },
-        }
-        std::process::exit(1);
-    };
-    let mut config_dir = config::config_dir().map_err(display_error_and_exit).unwrap();
-    if custom_repo {
-        config_dir.push("fdroid-custom-repos");
-        config::create_dir(&config_dir).map_err(display_error_and_exit).unwrap();
-        let mut s = DefaultHasher::new();
-        repo.hash(&mut s);
-        config_dir.push(format!("{}", s.finish()));
-        config::create_dir(&config_dir).map_err(display_error_and_exit).unwrap();
-    }
+    let config_dir = index_config_dir(if custom_repo { Some(&repo) } else { None }).map_err(|err| match err {
+        ConfigDirError::NotFound => "Could not find a config directory for apkeep to store F-Droid package index.".to_string(),
+        ConfigDirError::CouldNotCreate => "Could not create a config directory for apkeep to store F-Droid package index.".to_string(),
+    })?;
 
     let mut latest_etag_file = PathBuf::from(&config_dir);
     if use_entry {
@@ -90,56 +177,81 @@ async fn retrieve_index_or_exit(options: &HashMap<&str, &str>, mp: Rc<MultiProgr
         Ok(mut file) => {
             let mut contents = String::new();
             if file.read_to_string(&mut contents).is_err() {
-                print_error("Could not read etag file for F-Droid package index. Exiting.", output_format);
-                std::process::exit(1);
+                return Err("Could not read etag file for F-Droid package index.".to_string());
             }
             Some(contents)
         },
         Err(_) => None,
     };
 
+    let verify_index = match options.get("verify-index") {
+        Some(&"false") => false,
+        _ => true,
+    };
+    let cache = CachedIndex {
+        index_file: config_dir.join(if use_entry { "index.json" } else { "index_v1.json" }),
+        jar_file: config_dir.join(if use_entry { "entry.jar" } else { "index-v1.jar" }),
+        fingerprint: fingerprint.clone(),
+        use_entry,
+        verify_index,
+    };
+    let index_file = cache.index_file.clone();
+    if offline {
+        if !index_file.is_file() {
+            return Err("No cached F-Droid package index is available to use offline. Run once without --offline to download it.".to_string());
+        }
+        return read_cached_index(&cache, max_index_age, Rc::clone(&mp));
+    }
+
     let http_client = reqwest::Client::new();
     let fdroid_jar_url = if use_entry {
         format!("{}/entry.jar", repo)
     } else {
         format!("{}/index-v1.jar", repo)
     };
-    let jar_response = http_client
-        .head(fdroid_jar_url)
-        .send().await.unwrap();
+    let jar_response = match http_client.head(fdroid_jar_url).send().await {
+        Ok(jar_response) => jar_response,
+        Err(_) if index_file.is_file() => {
+            mp.suspend(|| eprintln!("Could not reach the F-Droid repository. Falling back to the cached package index."));
+            return read_cached_index(&cache, max_index_age, Rc::clone(&mp));
+        },
+        Err(_) => {
+            return Err("Could not reach the F-Droid repository, and no cached package index is available.".to_string());
+        },
+    };
 
     let etag = if jar_response.headers().contains_key("ETag") {
         jar_response.headers()["ETag"].to_str().unwrap()
     } else {
-        print_error("Could not receive etag for F-Droid package index. Exiting.", output_format);
-        std::process::exit(1);
+        return Err("Could not receive etag for F-Droid package index.".to_string());
     };
 
-    let mut index_file = PathBuf::from(&config_dir);
-    if use_entry {
-        index_file.push("index.json");
+    // The cached index is only trusted if it still matches the signed jar it was downloaded with.
+    // If it does not, it is downloaded again.
+    let cached_index = if latest_etag.as_deref() == Some(etag) {
+        match cache.load(Rc::clone(&mp)) {
+            Ok(index) => Some(index),
+            Err(err) => {
+                mp.suspend(|| eprintln!("The cached F-Droid package index could not be verified ({}). Downloading it again.", err));
+                None
+            },
+        }
     } else {
-        index_file.push("index_v1.json");
-    }
-    if latest_etag.is_some() && latest_etag.unwrap() == etag {
-        let index = read_file_to_string(index_file);
-        serde_json::from_str(&index).unwrap()
+        None
+    };
+    let index: Value = if let Some(index) = cached_index {
+        index
     } else {
-        let files = download_and_extract_to_tempdir(&temp_dir, &repo, Rc::clone(&mp), use_entry, output_format.clone()).await;
-        let verify_index = match options.get("verify-index") {
-            Some(&"false") => false,
-            _ => true,
-        };
+        let files = download_and_extract_to_tempdir(&temp_dir, &repo, Rc::clone(&mp), use_entry).await?;
         match verify_and_return_json(&temp_dir, &files, &fingerprint, verify_index, use_entry, Rc::clone(&mp)) {
             Ok(json) => {
                 let index = if use_entry {
-                    match verify_and_return_index_from_entry(&temp_dir, &repo, &json, verify_index, mp, output_format.clone()).await {
+                    match verify_and_return_index_from_entry(&temp_dir, &repo, &json, verify_index, mp).await {
                         Ok(index_from_entry) => {
                             index_from_entry
                         }
-                        Err(_) => {
-                            print_error("Could verify and return package index from entry JSON. Exiting.", output_format);
-                            std::process::exit(1);
+                        Err(err) => {
+                            return Err(err.to_string());
                         }
                     }
                 } else {
@@ -148,28 +260,105 @@ async fn retrieve_index_or_exit(options: &HashMap<&str, &str>, mp: Rc<MultiProgr
 
                 match serde_json::from_str(&index) {
                     Ok(index_value) => {
-                        if fs::write(index_file, index).is_err() {
-                            print_error("Could not write F-Droid package index to config file. Exiting.", output_format);
-                            std::process::exit(1);
+                        if fs::write(&index_file, index).is_err() {
+                            return Err("Could not write F-Droid package index to config file.".to_string());
+                        }
+                        if fs::copy(temp_dir.path().join(JAR_LOCAL_FILE), &cache.jar_file).is_err() {
+                            return Err("Could not write signed F-Droid package index to config file.".to_string());
                         }
                         if fs::write(latest_etag_file, etag).is_err() {
-                            print_error("Could not write F-Droid etag to config file. Exiting.", output_format);
-                            std::process::exit(1);
+                            return Err("Could not write F-Droid etag to config file.".to_string());
                         }
                         index_value
                     }
                     Err(_) => {
-                        print_error("Could not decode JSON for F-Droid package index. Exiting.", output_format);
-                        std::process::exit(1);
+                        return Err("Could not decode JSON for F-Droid package index.".to_string());
                     }
                 }
             },
             Err(_) => {
-                print_error("Could not verify F-Droid package index. Exiting.", output_format);
-                std::process::exit(1);
+                return Err("Could not verify F-Droid package index.".to_string());
             },
         }
+    };
+    if let Some(max_index_age) = max_index_age {
+        let index_age = index_age_secs(&index, &index_file);
+        if index_age > max_index_age {
+            return Err(format!("The F-Droid package index was published {} ago, which is older than the maximum index age of {}.", format_duration_secs(index_age), format_duration_secs(max_index_age)));
+        }
     }
+    Ok(index)
+}
+
+/// The package index cached in the config directory, along with the signed jar it was downloaded
+/// with.
+struct CachedIndex {
+    index_file: PathBuf,
+    jar_file: PathBuf,
+    /// The fingerprint the repository's signing certificate is pinned to
+    fingerprint: Vec<u8>,
+    use_entry: bool,
+    verify_index: bool,
+}
+
+impl CachedIndex {
+    /// Reads the cached index, first checking the signature on the cached jar against the pinned
+    /// fingerprint and that the index matches the digest the jar lists for it.  Anyone able to
+    /// write to the config directory could otherwise add packages to the index.
+    fn load(&self, mp: Rc<MultiProgress>) -> Result<Value, Box<dyn Error>> {
+        let index = if self.verify_index {
+            if !self.jar_file.is_file() {
+                return Err(Box::new(SimpleError::new("no signed jar is cached alongside it")));
+            }
+            let temp_dir = tempdir()?;
+            let files = extract_jar(&temp_dir, &self.jar_file)?;
+            let json = verify_and_return_json(&temp_dir, &files, &self.fingerprint, true, self.use_entry, mp)?;
+            if self.use_entry {
+                let (_, index_sha256) = entry_index(&json)?;
+                let index = fs::read(&self.index_file)?;
+                if hex::decode(index_sha256)? != Sha256::digest(&index)[..] {
+                    return Err(Box::new(SimpleError::new("it does not match the sha256sum in the signed entry file")));
+                }
+                String::from_utf8(index)?
+            } else {
+                // The index is the signed JSON itself, so there is nothing else to compare
+                json
+            }
+        } else {
+            read_file_to_string(self.index_file.clone())
+        };
+        Ok(serde_json::from_str(&index)?)
+    }
+}
+
+/// Loads the package index cached by a previous run without contacting the repository, reporting
+/// how old it is.  Fails if it is older than `max_index_age` seconds.
+fn read_cached_index(cache: &CachedIndex, max_index_age: Option<u64>, mp: Rc<MultiProgress>) -> Result<Value, String> {
+    let index = cache.load(Rc::clone(&mp))
+        .map_err(|err| format!("Could not verify the cached F-Droid package index: {}.", err))?;
+    let index_age = index_age_secs(&index, &cache.index_file);
+    if let Some(max_index_age) = max_index_age {
+        if index_age > max_index_age {
+            return Err(format!("The cached F-Droid package index was published {} ago, which is older than the maximum index age of {}.", format_duration_secs(index_age), format_duration_secs(max_index_age)));
+        }
+    }
+    mp.suspend(|| eprintln!("Using the cached F-Droid package index, published {} ago.", format_duration_secs(index_age)));
+    Ok(index)
+}
+
+/// The age of an index in seconds, going by the timestamp the repository gave it, or failing that
+/// when it was cached.
+fn index_age_secs(index: &Value, index_file: &Path) -> u64 {
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+    let timestamp = index.get("repo")
+        .and_then(|repo| repo.get("timestamp"))
+        .and_then(Value::as_u64)
+        .or_else(|| {
+            let modified = fs::metadata(index_file).and_then(|metadata| metadata.modified()).ok()?;
+            modified.duration_since(std::time::UNIX_EPOCH).ok().map(|since_epoch| since_epoch.as_millis() as u64)
+        })
+        .unwrap_or(now);
+    now.saturating_sub(timestamp) / 1000
 }
 
 fn print_error(err_msg: &str, output_format: OutputFormat) {
@@ -193,26 +382,104 @@ pub async fn download_apps(
     outpath: &Path,
     options: HashMap<&str, &str>,
 ) {
+    download(AppSelection::Listed(apps), parallel, sleep_duration, outpath, options).await;
+}
+
+/// Downloads every app in the repository index, or those matching the same filter options as
+/// `search`.
+pub async fn download_all_apps(
+    parallel: usize,
+    sleep_duration: u64,
+    outpath: &Path,
+    options: HashMap<&str, &str>,
+) {
+    let filter = match PackageFilter::from_options(None, &options) {
+        Ok(filter) => filter,
+        Err(err) => {
+            println!("{}. Exiting.", err);
+            std::process::exit(1);
+        },
+    };
+    download(AppSelection::Matching(filter), parallel, sleep_duration, outpath, options).await;
+}
+
+async fn download(
+    apps: AppSelection,
+    parallel: usize,
+    sleep_duration: u64,
+    outpath: &Path,
+    options: HashMap<&str, &str>,
+) {
+    let unverified_policy = match UnverifiedPolicy::from_options(&options) {
+        Ok(unverified_policy) => unverified_policy,
+        Err(err) => {
+            println!("{}. Exiting.", err);
+            std::process::exit(1);
+        },
+    };
+    let version_count = match VersionCount::from_options(&options) {
+        Ok(version_count) => version_count,
+        Err(err) => {
+            println!("{}. Exiting.", err);
+            std::process::exit(1);
+        },
+    };
+    let version_policy = match VersionPolicy::from_options(&options) {
+        Ok(version_policy) => version_policy,
+        Err(err) => {
+            println!("{}. Exiting.", err);
+            std::process::exit(1);
+        },
+    };
     let mp = Rc::new(MultiProgress::new());
     let index = retrieve_index_or_exit(&options, Rc::clone(&mp), OutputFormat::Plaintext).await;
 
-    let app_arch = options.get("arch").map(|x| x.to_string());
-    let (fdroid_apps, repo_address) = match parse_json_for_download_information(index, apps, app_arch.clone(), Rc::clone(&mp)) {
-        Ok((fdroid_apps, repo_address)) => (fdroid_apps, repo_address),
+    let abi_preference = AbiPreference::from_options(&options);
+    let listed = matches!(apps, AppSelection::Listed(_));
+    let (mut fdroid_apps, mut metadata, mut missing) = match parse_json_for_download_information(index, apps, version_count, &version_policy, abi_preference.as_ref(), Rc::clone(&mp)) {
+        Ok(download_information) => download_information,
         Err(_) => {
             println!("Could not parse JSON of F-Droid package index. Exiting.");
             std::process::exit(1);
         },
     };
+    // Old versions are moved to the archive repository, so apps which were asked for by name but
+    // could not be found are looked for there before giving up on them
+    if listed && !missing.is_empty() {
+        mp.suspend(|| println!("{} app(s) could not be found in the F-Droid repository. Checking its archive...", missing.len()));
+        if let Some(archive_index) = retrieve_archive_index(&options, Rc::clone(&mp)).await {
+            let apps = missing.iter().map(|missing_app| missing_app.app.clone()).collect();
+            match parse_json_for_download_information(archive_index, AppSelection::Listed(apps), version_count, &version_policy, abi_preference.as_ref(), Rc::clone(&mp)) {
+                Ok((archive_apps, archive_metadata, archive_missing)) => {
+                    fdroid_apps.extend(archive_apps);
+                    metadata.extend(archive_metadata);
+                    // Why an app is missing from the main repository is more telling than why it
+                    // is missing from the archive
+                    missing.retain(|missing_app| archive_missing.iter().any(|archive_missing_app| archive_missing_app.app == missing_app.app));
+                },
+                Err(_) => {
+                    println!("Could not parse JSON of F-Droid archive package index. Exiting.");
+                    std::process::exit(1);
+                },
+            }
+        }
+    }
+    for missing_app in &missing {
+        mp.suspend(|| println!("{} Skipping...", missing_app.reason));
+    }
 
-    let repo_address = Rc::new(repo_address);
-    futures_util::stream::iter(
+    let download_source = matches!(options.get("source"), Some(val) if val == &"1" || val.to_lowercase() == "true");
+    let download_build_log = matches!(options.get("build_log"), Some(val) if val == &"1" || val.to_lowercase() == "true");
+    let download_metadata = matches!(options.get("metadata"), Some(val) if val == &"1" || val.to_lowercase() == "true");
+    if download_metadata && matches!(options.get("use_entry"), Some(val) if val == &"0" || val.to_lowercase() == "false") {
+        println!("Metadata can only be downloaded from index-v2, which is not used when use_entry is false. Exiting.");
+        std::process::exit(1);
+    }
+    let results = futures_util::stream::iter(
         fdroid_apps.into_iter().map(|fdroid_app| {
-            let (app_id, app_version, url_filename, hash) = fdroid_app;
-            let repo_address = Rc::clone(&repo_address);
+            let FDroidApp { repo_address, app_id, app_version, arch: app_arch, version_code, filename: url_filename, hash, signers, source } = fdroid_app;
             let mp_log = Rc::clone(&mp);
             let mp = Rc::clone(&mp);
-            let app_arch = app_arch.clone();
             async move {
                 let app_string = match (app_version, app_arch) {
                     (None, None) => {
@@ -232,159 +499,358 @@ pub async fn download_apps(
                         format!("{}@{}@{}", app_id, version, arch)
                     },
                 };
-                let fname = format!("{}.apk", app_string);
+                let file_stem = sanitize_filename_component(&app_string);
+                let fname = format!("{}.apk", file_stem);
                 if sleep_duration > 0 {
                     sleep(Duration::from_millis(sleep_duration)).await;
                 }
+                let mut results = vec![];
                 let download_url = format!("{}/{}", repo_address, url_filename);
-                match AsyncDownload::new(&download_url, Path::new(outpath), &fname).get().await {
-                    Ok(mut dl) => {
-                        let length = dl.length();
-                        let cb = match length {
-                            Some(length) => Some(progress_wrapper(mp)(fname.clone(), length)),
-                            None => None,
-                        };
-
-                        let sha256sum = match dl.download_and_return_sha256sum(&cb).await {
-                            Ok(sha256sum) => Some(sha256sum),
-                            Err(err) if matches!(err.kind(), TDSTDErrorKind::FileExists) => {
-                                mp_log.println(format!("File already exists for {}. Skipping...", app_string)).unwrap();
-                                None
-                            },
-                            Err(err) if matches!(err.kind(), TDSTDErrorKind::PermissionDenied) => {
-                                mp_log.println(format!("Permission denied when attempting to write file for {}. Skipping...", app_string)).unwrap();
-                                None
-                            },
-                            Err(_) => {
-                                mp_log.println(format!("An error has occurred attempting to download {}.  Retry #1...", app_string)).unwrap();
-                                match AsyncDownload::new(&download_url, Path::new(outpath), &fname).download_and_return_sha256sum(&cb).await {
-                                    Ok(sha256sum) => Some(sha256sum),
-                                    Err(_) => {
-                                        mp_log.println(format!("An error has occurred attempting to download {}.  Retry #2...", app_string)).unwrap();
-                                        match AsyncDownload::new(&download_url, Path::new(outpath), &fname).download_and_return_sha256sum(&cb).await {
-                                            Ok(sha256sum) => Some(sha256sum),
-                                            Err(_) => {
-                                                mp_log.println(format!("An error has occurred attempting to download {}. Skipping...", app_string)).unwrap();
-                                                None
-                                            }
-                                        }
-                                    }
-                                }
+                if let Some(sha256sum) = download_with_retries::<Sha256>(&download_url, outpath, &fname, &app_string, Rc::clone(&mp)).await {
+                    match verify_download(&outpath.join(&fname), &sha256sum, &hash, &signers) {
+                        Ok(note) => {
+                            match note {
+                                Some(note) => mp_log.suspend(|| println!("{} downloaded successfully! {}", app_string, note)),
+                                None => mp_log.suspend(|| println!("{} downloaded successfully!", app_string)),
                             }
-                        };
-                        if let Some(sha256sum) = sha256sum {
-                            if sha256sum == hash {
-                                mp_log.suspend(|| println!("{} downloaded successfully!", app_string));
+                            results.push(Ok(()));
+                        },
+                        Err(reason) => results.push(Err(apply_unverified_policy(unverified_policy, outpath, &fname, app_string.to_string(), reason, &mp_log))),
+                    }
+                }
+
+                if download_source {
+                    match &source {
+                        Some(source) => {
+                            let source_string = format!("{} source", app_string);
+                            let source_fname = if source.name.ends_with(".tar.gz") {
+                                format!("{}_src.tar.gz", file_stem)
                             } else {
-                                mp_log.suspend(|| println!("{} downloaded, but the sha256sum does not match the one signed by F-Droid. Proceed with caution.", app_string));
+                                format!("{}_src.{}", file_stem, Path::new(&source.name).extension().unwrap_or_default().to_string_lossy())
+                            };
+                            let source_url = format!("{}/{}", repo_address, source.name);
+                            if let Some(sha256sum) = download_with_retries::<Sha256>(&source_url, outpath, &source_fname, &source_string, Rc::clone(&mp)).await {
+                                match source.sha256.as_ref().map(hex::decode) {
+                                    Some(Ok(source_hash)) if source_hash == sha256sum => {
+                                        mp_log.suspend(|| println!("{} downloaded successfully!", source_string));
+                                        results.push(Ok(()));
+                                    },
+                                    Some(_) => {
+                                        let reason = "the sha256sum does not match the one signed by F-Droid".to_string();
+                                        results.push(Err(apply_unverified_policy(unverified_policy, outpath, &source_fname, source_string, reason, &mp_log)));
+                                    },
+                                    None => {
+                                        mp_log.suspend(|| println!("{} downloaded, but the F-Droid index does not list its sha256sum, so it was not verified.", source_string));
+                                    },
+                                }
                             }
+                        },
+                        None => {
+                            mp_log.suspend(|| println!("The F-Droid index does not list a source tarball for {}. Skipping...", app_string));
+                        },
+                    }
+                }
+
+                if download_build_log {
+                    if let Some(version_code) = version_code {
+                        // Build logs are published next to the APK, but are not listed in the index
+                        let log_string = format!("{} build log", app_string);
+                        let log_url = format!("{}/{}_{}.log.gz", repo_address, app_id, version_code);
+                        if download_with_retries::<Sha256>(&log_url, outpath, &format!("{}.log.gz", file_stem), &log_string, Rc::clone(&mp)).await.is_some() {
+                            mp_log.suspend(|| println!("{} downloaded successfully!", log_string));
                         }
-                    },
-                    Err(_) => {
-                        mp_log.println(format!("Invalid response for {}. Skipping...", app_string)).unwrap();
-                    },
+                    }
                 }
+                results
             }
         })
-    ).buffer_unordered(parallel).collect::<Vec<()>>().await;
+    ).buffer_unordered(parallel).collect::<Vec<Vec<Result<(), UnverifiedDownload>>>>().await;
+
+    let metadata_results = if download_metadata {
+        futures_util::stream::iter(
+            metadata.into_iter().map(|package_metadata| {
+                download_package_metadata(package_metadata, outpath, unverified_policy, Rc::clone(&mp))
+            })
+        ).buffer_unordered(parallel).collect::<Vec<Vec<Result<(), UnverifiedDownload>>>>().await
+    } else {
+        vec![]
+    };
+    let results = results.into_iter().chain(metadata_results);
+
+    let mut verified = 0;
+    let mut unverified = vec![];
+    for result in results.flatten() {
+        match result {
+            Ok(()) => verified += 1,
+            Err(unverified_download) => unverified.push(unverified_download),
+        }
+    }
+    let status = print_run_report(verified, &unverified, unverified_policy);
+    if status != 0 {
+        std::process::exit(status);
+    }
+}
+
+/// Writes the localized texts of a package and downloads its graphics into
+/// `metadata/<package>/<locale>/` within `outpath`, verifying each graphic against the sha256sum in
+/// the index.  Single images are named for their kind, such as `icon.png`, and screenshots keep
+/// their names within a directory such as `phoneScreenshots/`.
+async fn download_package_metadata(metadata: PackageMetadata, outpath: &Path, unverified_policy: UnverifiedPolicy, mp: Rc<MultiProgress>) -> Vec<Result<(), UnverifiedDownload>> {
+    let mp_log = Rc::clone(&mp);
+    let PackageMetadata { repo_address, app_id, localized_texts, graphics } = metadata;
+    // Package IDs, locales, kinds and file names come from the index, so they must not be able to
+    // escape the package directory
+    let is_safe = |component: &str| !component.is_empty() && component != "." && component != ".." && !component.contains(['/', '\\']);
+    if !is_valid_package_id(&app_id) {
+        mp_log.println(format!("\"{}\" is not a valid package ID, so its metadata was not downloaded. Skipping...", app_id)).unwrap();
+        return vec![];
+    }
+    let package_dir = Path::new("metadata").join(&app_id);
+    mp_log.suspend(|| println!("Downloading metadata for {}...", app_id));
+
+    for text in localized_texts {
+        if !is_safe(&text.locale) {
+            continue;
+        }
+        let locale_dir = outpath.join(&package_dir).join(&text.locale);
+        if fs::create_dir_all(&locale_dir).and_then(|_| fs::write(locale_dir.join(format!("{}.txt", text.field)), text.text)).is_err() {
+            mp_log.println(format!("Could not write the {} {} of {}. Skipping...", text.locale, text.field, app_id)).unwrap();
+        }
+    }
+
+    let mut results = vec![];
+    for graphic in graphics {
+        let file_name = match Path::new(&graphic.name).file_name().and_then(|file_name| file_name.to_str()) {
+            Some(file_name) if is_safe(&graphic.locale) && is_safe(&graphic.kind) && is_safe(file_name) => file_name,
+            _ => continue,
+        };
+        let relative_path = if graphic.is_screenshot {
+            package_dir.join(&graphic.locale).join(&graphic.kind).join(file_name)
+        } else {
+            match Path::new(file_name).extension() {
+                Some(extension) => package_dir.join(&graphic.locale).join(format!("{}.{}", graphic.kind, extension.to_string_lossy())),
+                None => package_dir.join(&graphic.locale).join(&graphic.kind),
+            }
+        };
+        if let Some(parent) = relative_path.parent() {
+            if fs::create_dir_all(outpath.join(parent)).is_err() {
+                mp_log.println(format!("Could not create the metadata directory for {}. Skipping...", app_id)).unwrap();
+                continue;
+            }
+        }
+        let fname = relative_path.to_string_lossy().to_string();
+        let graphic_string = format!("{} {}", app_id, relative_path.strip_prefix(&package_dir).unwrap_or(&relative_path).display());
+        let graphic_url = format!("{}/{}", repo_address, graphic.name);
+        if let Some(sha256sum) = download_with_retries::<Sha256>(&graphic_url, outpath, &fname, &graphic_string, Rc::clone(&mp)).await {
+            match graphic.sha256.as_ref().map(hex::decode) {
+                Some(Ok(graphic_hash)) if graphic_hash == sha256sum => results.push(Ok(())),
+                Some(_) => {
+                    let reason = "the sha256sum does not match the one signed by F-Droid".to_string();
+                    results.push(Err(apply_unverified_policy(unverified_policy, outpath, &fname, graphic_string, reason, &mp_log)));
+                },
+                None => {
+                    mp_log.suspend(|| println!("{} downloaded, but the F-Droid index does not list its sha256sum, so it was not verified.", graphic_string));
+                },
+            }
+        }
+    }
+    mp_log.suspend(|| println!("Metadata for {} downloaded!", app_id));
+    results
+}
+
+/// Checks a downloaded APK against the sha256sum and signers listed in the index, returning the
+/// reason it failed verification if it did.  An APK which passes may come with a note on what
+/// could not be checked.
+fn verify_download(apk: &Path, sha256sum: &[u8], hash: &[u8], signers: &[String]) -> Result<Option<String>, String> {
+    if sha256sum != hash {
+        return Err("the sha256sum does not match the one signed by F-Droid".to_string());
+    }
+    if signers.is_empty() {
+        return Ok(Some("The F-Droid index does not list its signer, so the APK signature was not checked.".to_string()));
+    }
+    match apk_signature::verify_signers(apk) {
+        Ok(ApkSigners::Verified(mut apk_signers)) => {
+            apk_signers.sort();
+            apk_signers.dedup();
+            if apk_signers == signers {
+                Ok(None)
+            } else {
+                Err("it is not signed by the key listed in the F-Droid index".to_string())
+            }
+        },
+        // The sha256sum signed by F-Droid still vouches for the APK
+        Ok(ApkSigners::Unsupported(reason)) => Ok(Some(format!("Its APK signature could not be checked, since {}, so it was only verified against the sha256sum signed by F-Droid.", reason))),
+        Err(err) => Err(format!("its APK signature could not be verified ({})", err)),
+    }
 }
 
-type DownloadInformation = (Vec<(String, Option<String>, String, Vec<u8>)>, String);
+struct FDroidApp {
+    /// The address of the repository the app is downloaded from, which is the archive repository
+    /// for versions which are no longer in the main one
+    repo_address: String,
+    app_id: String,
+    app_version: Option<String>,
+    /// The ABI the APK was chosen for, when an ABI preference was given and the APK has native code
+    arch: Option<String>,
+    version_code: Option<u64>,
+    filename: String,
+    hash: Vec<u8>,
+    /// Lowercase hex sha256 fingerprints of the signing certificates, sorted and deduplicated
+    signers: Vec<String>,
+    source: Option<SourceFile>,
+}
+
+enum AppSelection {
+    /// Apps given on the command line or in a CSV, optionally with a version
+    Listed(Vec<(String, Option<String>)>),
+    /// Every package in the index matching the filter
+    Matching(PackageFilter),
+}
+
+/// The localized texts and graphics of a package, downloaded with the `metadata` option.
+struct PackageMetadata {
+    repo_address: String,
+    app_id: String,
+    localized_texts: Vec<LocalizedText>,
+    graphics: Vec<Graphic>,
+}
+
+/// A requested app which could not be found in an index, and why.
+struct MissingApp {
+    app: (String, Option<String>),
+    reason: String,
+}
+
+type DownloadInformation = (Vec<FDroidApp>, Vec<PackageMetadata>, Vec<MissingApp>);
 /// This currently works for `index-v1.json` as well as an index with version `20002`.  It is
 /// flexible enough to parse either, and may work on future index versions as well.  Since `sha256`
 /// digests are checked before proceeding, I don't foresee this having an insecure failure mode, so
 /// checking the index version and making the parsing overly brittle has no substantive advantage.
-fn parse_json_for_download_information(index: Value, apps: Vec<(String, Option<String>)>, app_arch: Option<String>, mp_log: Rc<MultiProgress>) -> Result<DownloadInformation, FDroidError> {
-    let index_map = index.as_object().ok_or(FDroidError::Dummy)?;
-    let repo_address = index_map
-        .get("repo").ok_or(FDroidError::Dummy)?
-        .get("address").ok_or(FDroidError::Dummy)?
-        .as_str().ok_or(FDroidError::Dummy)?;
-
-    let packages = index_map
-        .get("packages").ok_or(FDroidError::Dummy)?
-        .as_object().ok_or(FDroidError::Dummy)?;
-
-    let fdroid_apps: Vec<(String, Option<String>, String, Vec<u8>)> = apps.into_iter().map(|app| {
+fn parse_json_for_download_information(index: Value, apps: AppSelection, version_count: VersionCount, version_policy: &VersionPolicy, abi_preference: Option<&AbiPreference>, mp_log: Rc<MultiProgress>) -> Result<DownloadInformation, FDroidError> {
+    let index = Index::from_value(&index)?;
+
+    let apps = match apps {
+        AppSelection::Listed(apps) => apps,
+        AppSelection::Matching(filter) => {
+            let apps: Vec<(String, Option<String>)> = index.packages.iter()
+                .filter(|(package_id, package)| filter.matches(package_id, package))
+                .map(|(package_id, _)| (package_id.to_string(), None))
+                .collect();
+            mp_log.println(format!("Found {} matching package(s) in the F-Droid index.", apps.len())).unwrap();
+            apps
+        },
+    };
+
+    let mut missing = vec![];
+    let fdroid_apps: Vec<FDroidApp> = apps.into_iter().flat_map(|app| {
         let (app_id, app_version) = app;
-        match packages.get(&app_id) {
-            Some(Value::Array(app_array)) => {
-                for single_app in app_array {
-                    if let Value::Object(fdroid_app) = single_app {
-                        if let Some(Value::String(version_name)) = fdroid_app.get("versionName") {
-                            if app_version.is_none() || version_name == app_version.as_ref().unwrap() {
-                                if let (Some(Value::String(filename)), Some(Value::String(hash))) = (fdroid_app.get("apkName"), fdroid_app.get("hash")) {
-                                    if let Ok(hash) = hex::decode(hash.to_string()) {
-                                        if let Some(arch) = &app_arch {
-                                            if let Some(Value::Array(nativecode_array)) = fdroid_app.get("nativecode") {
-                                                if nativecode_array.iter().any(|value| {
-                                                    if let Value::String(value_str) = value{
-                                                        value_str == arch
-                                                    } else {
-                                                        false
-                                                    }
-                                                }) {
-                                                    return Some((app_id, app_version, filename.to_string(), hash));
-                                                }
-                                            }
-                                        } else {
-                                            return Some((app_id, app_version, filename.to_string(), hash));
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                let arch_str = app_arch.as_ref().map_or("".to_string(), |x| format!(" {}", x));
-                mp_log.println(format!("Could not find version {}{} of {}. Skipping...", app_version.unwrap(), arch_str, app_id)).unwrap();
-                return None;
+        let package = match index.packages.get(&app_id) {
+            Some(package) => package,
+            None => {
+                let reason = format!("Could not find {} in package list.", app_id);
+                missing.push(MissingApp { app: (app_id, app_version), reason });
+                return vec![];
             },
-            Some(Value::Object(app_object)) => {
-                if let Some(Value::Object(versions)) = app_object.get("versions") {
-                    let mut latest_version = 0;
-                    let mut filename = String::new();
-                    let mut hash = String::new();
-                    for (_, version_value) in versions {
-                        if let Value::Object(version) = version_value {
-                            if let (Some(Value::Object(manifest)), Some(Value::Object(file))) = (version.get("manifest"), version.get("file")) {
-                                if let (Some(Value::String(name)), Some(Value::String(sha256))) = (file.get("name"), file.get("sha256")) {
-                                    if app_version.is_some() {
-                                        if let Some(Value::String(version_name)) = manifest.get("versionName") {
-                                            if version_name == app_version.as_ref().unwrap() {
-                                                if let Ok(sha256) = hex::decode(sha256.to_string()) {
-                                                    return Some((app_id, app_version, name.to_string(), sha256));
-                                                }
-                                            }
-                                        }
-                                    } else {
-                                        if let Some(Value::Number(version_code_number)) = manifest.get("versionCode") {
-                                            if let Some(version_code) = version_code_number.as_u64() {
-                                                if version_code > latest_version {
-                                                    latest_version = version_code;
-                                                    filename = name.to_string();
-                                                    hash = sha256.to_string();
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    if app_version.is_none() {
-                        if let Ok(hash) = hex::decode(hash) {
-                            return Some((app_id, app_version, filename, hash));
-                        }
+        };
+        let version_spec = app_version.as_deref().map(VersionSpec::parse);
+        let mut candidates: Vec<&PackageVersion> = package.versions.iter().filter(|version| {
+            let version_matches = match &version_spec {
+                Some(version_spec) => version_matches(version_spec, version),
+                None => version_policy.allows(package, version),
+            };
+            let arch_matches = match abi_preference {
+                Some(abi_preference) => abi_preference.rank(&version.nativecode).is_some(),
+                None => true,
+            };
+            version_matches && arch_matches && !version.filename.is_empty() && hex::decode(&version.sha256).is_ok()
+        }).collect();
+        // Several APKs may share a version name, so the one with the highest version code is
+        // chosen rather than whichever is listed first
+        candidates.sort_by_key(|version| std::cmp::Reverse(version.version_code.unwrap_or(0)));
+        if let Some(abi_preference) = abi_preference {
+            // Per-ABI builds of a version have different version codes, so versions are instead
+            // ordered by the newest build of each, and only the build which best suits the device
+            // is kept
+            let mut newest_builds: HashMap<Option<&str>, u64> = HashMap::new();
+            for version in &candidates {
+                let newest_build = newest_builds.entry(version.version_name.as_deref()).or_default();
+                *newest_build = (*newest_build).max(version.version_code.unwrap_or(0));
+            }
+            candidates.sort_by_key(|version| (
+                std::cmp::Reverse(newest_builds[&version.version_name.as_deref()]),
+                abi_preference.rank(&version.nativecode),
+                std::cmp::Reverse(version.version_code.unwrap_or(0)),
+            ));
+            let mut seen_names = HashSet::new();
+            candidates.retain(|version| seen_names.insert(version.version_name.clone()));
+        }
+        let selected: Vec<&PackageVersion> = if app_version.is_some() {
+            candidates.into_iter().take(1).collect()
+        } else {
+            candidates.into_iter().take(version_count.take()).collect()
+        };
+        if selected.is_empty() {
+            let arch_str = abi_preference.map_or("".to_string(), |abi_preference| format!(" for {}", abi_preference));
+            let reason = match &app_version {
+                Some(app_version) => format!("Could not find version {}{} of {}.", app_version, arch_str, app_id),
+                None => format!("Could not find a version{} of {}.", arch_str, app_id),
+            };
+            missing.push(MissingApp { app: (app_id, app_version), reason });
+            return vec![];
+        }
+        // When only the latest version is wanted it is saved without a version in its filename, as
+        // it always has been.  Otherwise each is named for its version, and versions which share a
+        // name (as per-architecture builds often do), or which were requested by version code,
+        // also get their version code.
+        let latest_only = matches!(version_count, VersionCount::Latest(1));
+        selected.iter().map(|version| {
+            let version_name = version.version_name.clone().unwrap_or_default();
+            let with_code = format!("{}_{}", version_name, version.version_code.unwrap_or(0));
+            let app_version = match (&version_spec, latest_only) {
+                (Some(VersionSpec::Code(_)), _) => Some(with_code),
+                (Some(version_spec), _) => Some(version_spec.to_string()),
+                (None, true) => None,
+                (None, false) => {
+                    let shared_name = selected.iter()
+                        .filter(|other| other.version_name == version.version_name)
+                        .count() > 1;
+                    if shared_name || version_name.is_empty() {
+                        Some(with_code)
+                    } else {
+                        Some(version_name)
                     }
-                }
-            },
-            _ => mp_log.println(format!("Could not find {} in package list. Skipping...", app_id)).unwrap(),
+                },
+            };
+            FDroidApp {
+                repo_address: index.repo_address.to_string(),
+                app_id: app_id.to_string(),
+                app_version,
+                arch: abi_preference.and_then(|abi_preference| abi_preference.best_match(&version.nativecode)).map(String::from),
+                version_code: version.version_code,
+                filename: version.filename.clone(),
+                hash: hex::decode(&version.sha256).unwrap(),
+                signers: version.signers.clone(),
+                source: version.source.clone(),
+            }
+        }).collect()
+    }).collect();
+
+    let mut metadata: Vec<PackageMetadata> = vec![];
+    for fdroid_app in &fdroid_apps {
+        if metadata.iter().any(|package_metadata| package_metadata.app_id == fdroid_app.app_id) {
+            continue;
         }
-        None
-    }).flatten().collect();
+        if let Some(package) = index.packages.get(&fdroid_app.app_id) {
+            metadata.push(PackageMetadata {
+                repo_address: index.repo_address.to_string(),
+                app_id: fdroid_app.app_id.to_string(),
+                localized_texts: package.localized_texts.clone(),
+                graphics: package.graphics.clone(),
+            });
+        }
+    }
 
-    Ok((fdroid_apps, repo_address.to_string()))
+    Ok((fdroid_apps, metadata, missing))
 }
 
 pub async fn list_versions(apps: Vec<(String, Option<String>)>, options: HashMap<&str, &str>) {
@@ -393,22 +859,30 @@ pub async fn list_versions(apps: Vec<(String, Option<String>)>, options: HashMap
         Some(val) if val.to_lowercase() == "json" => OutputFormat::Json,
         _ => OutputFormat::Plaintext,
     };
-    let index = retrieve_index_or_exit(&options, mp, output_format.clone()).await;
-
-    if parse_json_display_versions(index, apps, output_format).is_err() {
+    let index = retrieve_index_or_exit(&options, Rc::clone(&mp), output_format.clone()).await;
+    let Ok(index) = Index::from_value(&index) else {
         eprintln!("Could not parse JSON of F-Droid package index. Exiting.");
         std::process::exit(1);
     };
+    // The archive repository is only needed for apps which are no longer in the main repository
+    let archive_index = if apps.iter().any(|(app_id, _)| !index.packages.contains_key(app_id)) {
+        match retrieve_archive_index(&options, mp).await.as_ref().map(Index::from_value) {
+            Some(Ok(archive_index)) => Some(archive_index),
+            Some(Err(_)) => {
+                eprintln!("Could not parse JSON of F-Droid archive package index. Exiting.");
+                std::process::exit(1);
+            },
+            None => None,
+        }
+    } else {
+        None
+    };
+
+    parse_json_display_versions(&index, archive_index.as_ref(), apps, output_format);
 }
 
 /// The comments for `parse_json_for_download_information` apply here, too.
-fn parse_json_display_versions(index: Value, apps: Vec<(String, Option<String>)>, output_format: OutputFormat) -> Result<(), FDroidError> {
-    let index_map = index.as_object().ok_or(FDroidError::Dummy)?;
-
-    let packages = index_map
-        .get("packages").ok_or(FDroidError::Dummy)?
-        .as_object().ok_or(FDroidError::Dummy)?;
-
+fn parse_json_display_versions(index: &Index, archive_index: Option<&Index>, apps: Vec<(String, Option<String>)>, output_format: OutputFormat) {
     let mut json_root = match output_format {
         OutputFormat::Json => Some(HashMap::new()),
         _ => None,
@@ -419,65 +893,386 @@ fn parse_json_display_versions(index: Value, apps: Vec<(String, Option<String>)>
         if output_format.is_plaintext() {
             println!("Versions available for {} on F-Droid:", app_id);
         }
-        let mut versions_set = HashSet::new();
-        match packages.get(&app_id) {
-            Some(Value::Array(app_array)) => {
-                for single_app in app_array {
-                    if let Value::Object(fdroid_app) = single_app {
-                        if let Some(Value::String(version_name)) = fdroid_app.get("versionName") {
-                            versions_set.insert(version_name.to_string());
-                        }
-                    }
+        // Each version is paired with whether it is in the archive repository
+        let mut versions: Vec<(&PackageVersion, bool)> = index.packages.get(&app_id).iter()
+            .flat_map(|package| package.versions.iter().map(|version| (version, false)))
+            .chain(archive_index.iter()
+                .filter_map(|archive_index| archive_index.packages.get(&app_id))
+                .flat_map(|package| package.versions.iter().map(|version| (version, true))))
+            .collect();
+        if versions.is_empty() {
+            match output_format {
+                OutputFormat::Plaintext => {
+                    eprintln!("| Could not find {} in package list. Skipping...", app_id);
+                },
+                OutputFormat::Json => {
+                    let mut app_root = HashMap::new();
+                    app_root.insert("error".to_string(), "Not found in package list.".to_string());
+                    json_root.as_mut().unwrap().insert(app_id.to_string(), json!(app_root));
                 }
-            },
-            Some(Value::Object(app_object)) => {
-                if let Some(Value::Object(versions)) = app_object.get("versions") {
-                    for (_, version_value) in versions {
-                        if let Value::Object(version) = version_value {
-                            if let Some(Value::Object(manifest)) = version.get("manifest") {
-                                if let Some(Value::String(version_name)) = manifest.get("versionName") {
-                                    versions_set.insert(version_name.to_string());
-                                }
-                            }
-                        }
+            }
+            continue;
+        }
+        // Every APK is listed, since several may share a version name but differ in their
+        // version code, ABIs or SDK levels
+        versions.sort_by_key(|(version, _)| std::cmp::Reverse(version.version_code.unwrap_or(0)));
+        match output_format {
+            OutputFormat::Plaintext => {
+                for (version, archived) in versions {
+                    let mut details = vec![if version.nativecode.is_empty() {
+                        "any ABI".to_string()
+                    } else {
+                        version.nativecode.join(" ")
+                    }];
+                    if let Some(size) = version.size {
+                        details.push(format_size(size));
+                    }
+                    if let Some(added) = version.added {
+                        details.push(format!("added {}", format_date_ms(added)));
+                    }
+                    if let Some(min_sdk) = version.min_sdk {
+                        details.push(format!("min SDK {}", min_sdk));
                     }
+                    if archived {
+                        details.push("archived".to_string());
+                    }
+                    println!("| {} (code {}): {}",
+                        version.version_name.as_deref().unwrap_or("unknown"),
+                        version.version_code.map_or("unknown".to_string(), |version_code| version_code.to_string()),
+                        details.join(", "));
                 }
             },
-            _ => {
+            OutputFormat::Json => {
+                let mut app_root: HashMap<String, Vec<Value>> = HashMap::new();
+                app_root.insert("available_versions".to_string(), versions.into_iter().map(|(version, archived)| {
+                    json!({
+                        "version": version.version_name,
+                        "version_code": version.version_code,
+                        "abis": version.nativecode,
+                        "size": version.size,
+                        "added": version.added.map(format_date_ms),
+                        "min_sdk": version.min_sdk,
+                        "archived": archived,
+                    })
+                }).collect());
+                json_root.as_mut().unwrap().insert(app_id.to_string(), json!(app_root));
+            }
+        }
+    }
+    if output_format.is_json() {
+        println!("{{\"source\":\"F-Droid\",\"apps\":{}}}", json!(json_root.unwrap()));
+    };
+}
+
+/// For each app, downloads every signed variant of a version listed in the index (for instance,
+/// both the developer-signed and the F-Droid-signed APK of a reproducible build) and reports
+/// whether their contents are identical apart from the signatures.
+pub async fn reproducible_report(apps: Vec<(String, Option<String>)>, options: HashMap<&str, &str>) {
+    let mp = Rc::new(MultiProgress::new());
+    let output_format = match options.get("output_format") {
+        Some(val) if val.to_lowercase() == "json" => OutputFormat::Json,
+        _ => OutputFormat::Plaintext,
+    };
+    let version_policy = match VersionPolicy::from_options(&options) {
+        Ok(version_policy) => version_policy,
+        Err(err) => {
+            print_error(&format!("{}. Exiting.", err), output_format);
+            std::process::exit(1);
+        },
+    };
+    let temp_dir = match tempdir() {
+        Ok(temp_dir) => temp_dir,
+        Err(_) => {
+            print_error("Could not create temporary directory for F-Droid APKs. Exiting.", output_format);
+            std::process::exit(1);
+        }
+    };
+    let index = retrieve_index_or_exit(&options, Rc::clone(&mp), output_format.clone()).await;
+    let index = match Index::from_value(&index) {
+        Ok(index) => index,
+        Err(_) => {
+            print_error("Could not parse JSON of F-Droid package index. Exiting.", output_format);
+            std::process::exit(1);
+        },
+    };
+
+    let mut json_apps = serde_json::Map::new();
+    for (app_id, app_version) in apps {
+        let package = match index.packages.get(&app_id) {
+            Some(package) => package,
+            None => {
                 match output_format {
-                    OutputFormat::Plaintext => {
-                        eprintln!("| Could not find {} in package list. Skipping...", app_id);
+                    OutputFormat::Plaintext => eprintln!("Could not find {} in package list. Skipping...", app_id),
+                    OutputFormat::Json => {
+                        json_apps.insert(app_id, json!({"error": "Not found in package list."}));
                     },
+                }
+                continue;
+            },
+        };
+        let selected = match app_version.as_deref().map(VersionSpec::parse) {
+            Some(version_spec) => package.versions.iter()
+                .filter(|version| version_matches(&version_spec, version))
+                .max_by_key(|version| version.version_code.unwrap_or(0)),
+            None => package.versions.iter()
+                .filter(|version| version_policy.allows(package, version))
+                .max_by_key(|version| version.version_code.unwrap_or(0)),
+        };
+        let (selected, version_name, version_code) = match selected {
+            Some(version) => (version, version.version_name.clone().unwrap_or_default(), version.version_code),
+            None => {
+                match output_format {
+                    OutputFormat::Plaintext => eprintln!("Could not find a matching version of {}. Skipping...", app_id),
                     OutputFormat::Json => {
-                        let mut app_root = HashMap::new();
-                        app_root.insert("error".to_string(), "Not found in package list.".to_string());
-                        json_root.as_mut().unwrap().insert(app_id.to_string(), json!(app_root));
-                    }
+                        json_apps.insert(app_id, json!({"error": "No matching version found."}));
+                    },
                 }
                 continue;
+            },
+        };
+        let variants: Vec<&PackageVersion> = package.versions.iter()
+            // Without a version code, there is nothing to tell another APK is a build of the same
+            // version, so only the selected APK is used
+            .filter(|version| match version_code {
+                Some(_) => version.version_code == version_code && version.version_name == selected.version_name,
+                None => std::ptr::eq(*version, selected),
+            })
+            .filter(|version| !version.filename.is_empty())
+            .collect();
+        if output_format.is_plaintext() {
+            println!("Reproducible build report for {} version {} ({}):", app_id, version_name, version_code.unwrap_or(0));
+        }
+
+        let mut downloaded = vec![];
+        let mut errors = vec![];
+        for variant in &variants {
+            let fname = variant.filename.replace('/', "_");
+            let download_url = format!("{}/{}", index.repo_address, variant.filename);
+            let hash = hex::decode(&variant.sha256).unwrap_or_default();
+            match download_with_retries::<Sha256>(&download_url, temp_dir.path(), &fname, &variant.filename, Rc::clone(&mp)).await {
+                Some(sha256sum) => match verify_download(&temp_dir.path().join(&fname), &sha256sum, &hash, &variant.signers) {
+                    Ok(_) => downloaded.push(fname),
+                    Err(reason) => errors.push(format!("{} was downloaded, but {}", variant.filename, reason)),
+                },
+                None => errors.push(format!("{} could not be downloaded", variant.filename)),
             }
         }
-        let mut versions_set = versions_set.drain().collect::<Vec<String>>();
-        versions_set.sort();
+
+        let mut differences = vec![];
+        let reproducible = if !errors.is_empty() || downloaded.len() < 2 {
+            None
+        } else {
+            for other in &downloaded[1..] {
+                match reproducible::differing_entries(&temp_dir.path().join(&downloaded[0]), &temp_dir.path().join(other)) {
+                    Ok(differing) => differences.extend(differing),
+                    Err(err) => errors.push(format!("{} could not be compared ({})", other, err)),
+                }
+            }
+            differences.sort();
+            differences.dedup();
+            if errors.is_empty() { Some(differences.is_empty()) } else { None }
+        };
+
         match output_format {
             OutputFormat::Plaintext => {
-                println!("| {}", versions_set.join(", "));
+                for variant in &variants {
+                    println!("| {} signed by {}", variant.filename, if variant.signers.is_empty() { "an unlisted signer".to_string() } else { variant.signers.join(", ") });
+                }
+                for error in &errors {
+                    println!("| {}", error);
+                }
+                match reproducible {
+                    Some(true) => println!("| Reproducible: the APKs are identical apart from their signatures"),
+                    Some(false) => println!("| Not reproducible: the APKs differ in {}", differences.join(", ")),
+                    None if errors.is_empty() => println!("| Only one signed variant is listed in the index, so there is nothing to compare"),
+                    None => println!("| Could not determine whether the build is reproducible"),
+                }
             },
             OutputFormat::Json => {
-                let mut app_root: HashMap<String, Vec<HashMap<String, String>>> = HashMap::new();
-                app_root.insert("available_versions".to_string(), versions_set.into_iter().map(|v| {
-                    let mut version_map = HashMap::new();
-                    version_map.insert("version".to_string(), v);
-                    version_map
-                }).collect());
-                json_root.as_mut().unwrap().insert(app_id.to_string(), json!(app_root));
-            }
+                json_apps.insert(app_id, json!({
+                    "version": version_name,
+                    "version_code": version_code,
+                    "variants": variants.iter().map(|variant| json!({
+                        "file": variant.filename,
+                        "signers": variant.signers,
+                    })).collect::<Vec<Value>>(),
+                    "reproducible": reproducible,
+                    "differences": differences,
+                    "errors": errors,
+                }));
+            },
+        }
+        for fname in downloaded {
+            let _ = fs::remove_file(temp_dir.path().join(fname));
         }
     }
     if output_format.is_json() {
-        println!("{{\"source\":\"F-Droid\",\"apps\":{}}}", json!(json_root.unwrap()));
+        println!("{}", json!({"source": "F-Droid", "apps": json_apps}));
+    }
+}
+
+pub async fn search(query: &str, options: HashMap<&str, &str>) {
+    let mp = Rc::new(MultiProgress::new());
+    let output_format = match options.get("output_format") {
+        Some(val) if val.to_lowercase() == "json" => OutputFormat::Json,
+        _ => OutputFormat::Plaintext,
+    };
+    let filter = match PackageFilter::from_options(Some(query), &options) {
+        Ok(filter) => filter,
+        Err(err) => {
+            print_error(&format!("{}. Exiting.", err), output_format);
+            std::process::exit(1);
+        },
     };
-    Ok(())
+    let index = retrieve_index_or_exit(&options, mp, output_format.clone()).await;
+    let index = match Index::from_value(&index) {
+        Ok(index) => index,
+        Err(_) => {
+            print_error("Could not parse JSON of F-Droid package index. Exiting.", output_format);
+            std::process::exit(1);
+        },
+    };
+
+    let results = index.packages.iter()
+        .filter(|(package_id, package)| filter.matches(package_id, package));
+    match output_format {
+        OutputFormat::Plaintext => {
+            println!("Search results on F-Droid:");
+            let mut found = false;
+            for (package_id, package) in results {
+                found = true;
+                let latest_version = package.latest_version()
+                    .and_then(|version| version.version_name.as_deref())
+                    .unwrap_or("unknown");
+                println!("| {} ({}) version {}", package_id, package.name.as_deref().unwrap_or(package_id), latest_version);
+                if let Some(summary) = &package.summary {
+                    println!("|   {}", summary);
+                }
+            }
+            if !found {
+                println!("| No matching packages found.");
+            }
+        },
+        OutputFormat::Json => {
+            let apps: serde_json::Map<String, Value> = results.map(|(package_id, package)| {
+                (package_id.to_string(), json!({
+                    "name": package.name,
+                    "summary": package.summary,
+                    "categories": package.categories,
+                    "anti_features": package.anti_features,
+                    "license": package.license,
+                    "author": package.author_name,
+                    "last_updated": package.last_updated.map(format_date_ms),
+                    "latest_version": package.latest_version().and_then(|version| version.version_name.clone()),
+                }))
+            }).collect();
+            println!("{}", json!({"source": "F-Droid", "apps": apps}));
+        },
+    }
+}
+
+/// Reports what changed between two package index snapshots, such as the `index.json` cached in
+/// `apkeep`'s config directory at different times.
+pub fn diff_indexes(old_index: &Path, new_index: &Path, options: HashMap<&str, &str>) {
+    let output_format = match options.get("output_format") {
+        Some(val) if val.to_lowercase() == "json" => OutputFormat::Json,
+        _ => OutputFormat::Plaintext,
+    };
+    let read_index = |index_file: &Path| {
+        let index = fs::read_to_string(index_file).ok()
+            .and_then(|index| serde_json::from_str::<Value>(&index).ok())
+            .and_then(|index| Index::from_value(&index).ok());
+        match index {
+            Some(index) => index,
+            None => {
+                print_error(&format!("Could not read an F-Droid package index from {}. Exiting.", index_file.display()), output_format.clone());
+                std::process::exit(1);
+            },
+        }
+    };
+    let old = read_index(old_index);
+    let new = read_index(new_index);
+    let index_diff = IndexDiff::between(&old, &new);
+
+    match output_format {
+        OutputFormat::Plaintext => {
+            println!("Changes to the F-Droid package index from {} to {}:", old_index.display(), new_index.display());
+            if index_diff.is_empty() {
+                println!("| No changes.");
+                return;
+            }
+            let describe_versions = |versions: &[VersionId]| {
+                versions.iter()
+                    .map(|version| format!("{} ({})", version.version_name.as_deref().unwrap_or("unknown"), version.version_code))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            };
+            println!("Added packages: {}", index_diff.added_packages.len());
+            for package_id in &index_diff.added_packages {
+                let latest_version = new.packages[package_id].latest_version()
+                    .and_then(|version| version.version_name.as_deref())
+                    .unwrap_or("unknown");
+                println!("| {} ({}) version {}", package_id, new.packages[package_id].name.as_deref().unwrap_or(package_id), latest_version);
+            }
+            println!("Removed packages: {}", index_diff.removed_packages.len());
+            for package_id in &index_diff.removed_packages {
+                println!("| {} ({})", package_id, old.packages[package_id].name.as_deref().unwrap_or(package_id));
+            }
+            println!("Changed packages: {}", index_diff.changed_packages.len());
+            for package_diff in &index_diff.changed_packages {
+                println!("| {}", package_diff.package_id);
+                if !package_diff.added_versions.is_empty() {
+                    println!("|   New versions: {}", describe_versions(&package_diff.added_versions));
+                }
+                if !package_diff.removed_versions.is_empty() {
+                    println!("|   Removed versions: {}", describe_versions(&package_diff.removed_versions));
+                }
+                if !package_diff.added_signers.is_empty() {
+                    println!("|   New signers: {}", package_diff.added_signers.join(", "));
+                }
+                if !package_diff.removed_signers.is_empty() {
+                    println!("|   Removed signers: {}", package_diff.removed_signers.join(", "));
+                }
+                if !package_diff.added_anti_features.is_empty() {
+                    println!("|   New anti-features: {}", package_diff.added_anti_features.join(", "));
+                }
+                if !package_diff.removed_anti_features.is_empty() {
+                    println!("|   Removed anti-features: {}", package_diff.removed_anti_features.join(", "));
+                }
+                if let Some((old_license, new_license)) = &package_diff.license {
+                    println!("|   License changed from {} to {}", old_license.as_deref().unwrap_or("none"), new_license.as_deref().unwrap_or("none"));
+                }
+            }
+        },
+        OutputFormat::Json => {
+            println!("{}", json!({"source": "F-Droid", "diff": index_diff.to_json()}));
+        },
+    }
+}
+
+/// Builds an F-Droid repository from the APKs in `outpath`, which F-Droid clients (or `apkeep`
+/// itself, with the `repo` option) can then download from.
+pub fn build_repo(outpath: &Path, options: HashMap<&str, &str>) {
+    println!("Building F-Droid repository in {}...", outpath.display());
+    match repo::build(outpath, &options, |message| println!("{}", message)) {
+        Ok(summary) => {
+            if let Some(key_file) = summary.generated_key {
+                println!("Generated a new repository signing key at {}. Keep it safe, as clients will only trust indexes signed by it.", key_file.display());
+            }
+            println!("Repository built with {} APK(s) of {} package(s).", summary.apks, summary.packages);
+            if !summary.skipped.is_empty() {
+                println!("{} file(s) could not be added:", summary.skipped.len());
+                for (filename, reason) in summary.skipped {
+                    println!("| {}: {}", filename, reason);
+                }
+            }
+            println!("Repository fingerprint: {}", summary.fingerprint.to_uppercase());
+        },
+        Err(err) => {
+            println!("Could not build F-Droid repository: {}. Exiting.", err);
+            std::process::exit(1);
+        },
+    }
 }
 
 fn verify_and_return_json(dir: &TempDir, files: &[String], fingerprint: &[u8], verify_index: bool, use_entry: bool, mp: Rc<MultiProgress>) -> Result<String, Box<dyn Error>> {
@@ -586,25 +1381,13 @@ fn verify_and_return_json(dir: &TempDir, files: &[String], fingerprint: &[u8], v
     Ok(String::from(std::str::from_utf8(&json_file_data)?))
 }
 
-async fn verify_and_return_index_from_entry(dir: &TempDir, repo: &str, json: &str, verify_index: bool, mp: Rc<MultiProgress>, output_format: OutputFormat) -> Result<String, Box<dyn Error>> {
+async fn verify_and_return_index_from_entry(dir: &TempDir, repo: &str, json: &str, verify_index: bool, mp: Rc<MultiProgress>) -> Result<String, Box<dyn Error>> {
     let mp_log = Rc::clone(&mp);
-    let (index_name, index_sha256) = match serde_json::from_str::<Value>(json) {
-        Ok(entry) => {
-            let entry_map = entry.as_object().ok_or(FDroidError::Dummy)?;
-            let index_map = entry_map
-                .get("index").ok_or(FDroidError::Dummy)?;
-            (index_map.get("name").ok_or(FDroidError::Dummy)?
-                .as_str().ok_or(FDroidError::Dummy)?.trim_start_matches("/").to_string(),
-            index_map.get("sha256").ok_or(FDroidError::Dummy)?
-                .as_str().ok_or(FDroidError::Dummy)?.to_string())
-        },
-        Err(_) => {
-            print_error("Could not decode JSON for F-Droid entry file. Exiting.", output_format);
-            std::process::exit(1);
-        }
-    };
+    let (index_name, index_sha256) = entry_index(json)
+        .map_err(|_| SimpleError::new("Could not decode JSON for F-Droid entry file."))?;
     let index_url = format!("{}/{}", repo, index_name);
-    let mut dl = AsyncDownload::new(&index_url, dir.path(), &index_name).get().await.unwrap();
+    let mut dl = AsyncDownload::new(&index_url, dir.path(), &index_name).get().await
+        .map_err(|_| SimpleError::new("Could not download F-Droid package index."))?;
     let length = dl.length();
     let cb = match length {
         Some(length) => Some(progress_wrapper(mp)(index_name.to_string(), length)),
@@ -623,13 +1406,8 @@ async fn verify_and_return_index_from_entry(dir: &TempDir, repo: &str, json: &st
                     hasher.update(index_file_data.clone());
                     Vec::from(hasher.finalize().as_slice())
                 };
-                let index_sha256 = match hex::decode(index_sha256) {
-                    Ok(index_sha256) => index_sha256,
-                    Err(_) => {
-                        print_error("Index sha256sum did not specify valid hex. Exiting.", output_format);
-                        std::process::exit(1);
-                    }
-                };
+                let index_sha256 = hex::decode(index_sha256)
+                    .map_err(|_| SimpleError::new("Index sha256sum did not specify valid hex."))?;
                 if index_sha256 != actual_index_shasum {
                     return Err(Box::new(SimpleError::new("The index sha256sum from the entry file does not match the actual index sha256sum.")));
                 }
@@ -637,13 +1415,22 @@ async fn verify_and_return_index_from_entry(dir: &TempDir, repo: &str, json: &st
 
             Ok(String::from(std::str::from_utf8(&index_file_data)?))
         }
-        Err(_) => {
-            print_error("Could not download F-Droid package index. Exiting.", output_format);
-            std::process::exit(1);
-        }
+        Err(_) => Err(Box::new(SimpleError::new("Could not download F-Droid package index."))),
     }
 }
 
+/// The name and sha256sum of the index listed in an entry file.
+fn entry_index(json: &str) -> Result<(String, String), Box<dyn Error>> {
+    let entry = serde_json::from_str::<Value>(json)?;
+    let entry_map = entry.as_object().ok_or(FDroidError::Dummy)?;
+    let index_map = entry_map
+        .get("index").ok_or(FDroidError::Dummy)?;
+    Ok((index_map.get("name").ok_or(FDroidError::Dummy)?
+        .as_str().ok_or(FDroidError::Dummy)?.trim_start_matches("/").to_string(),
+    index_map.get("sha256").ok_or(FDroidError::Dummy)?
+        .as_str().ok_or(FDroidError::Dummy)?.to_string()))
+}
+
 fn get_signed_data_from_cert_file(signature_block_file: PathBuf) -> Result<SignedData, Box<dyn Error>> {
     let bytes = fs::read(signature_block_file).unwrap();
     match SignedData::parse_ber(&bytes) {
@@ -670,68 +1457,63 @@ fn get_signed_data_from_cert_file(signature_block_file: PathBuf) -> Result<Signe
     }
 }
 
-async fn download_and_extract_to_tempdir(dir: &TempDir, repo: &str, mp: Rc<MultiProgress>, use_entry: bool, output_format: OutputFormat) -> Vec<String> {
+async fn download_and_extract_to_tempdir(dir: &TempDir, repo: &str, mp: Rc<MultiProgress>, use_entry: bool) -> Result<Vec<String>, String> {
     let mp_log = Rc::clone(&mp);
     mp_log.suspend(|| println!("Downloading F-Droid package repository..."));
-    let mut files = vec![];
     let fdroid_jar_url  = if use_entry {
         format!("{}/entry.jar", repo)
     } else {
         format!("{}/index-v1.jar", repo)
     };
-    let jar_local_file = "jar.zip";
-    let mut dl = AsyncDownload::new(&fdroid_jar_url, dir.path(), jar_local_file).get().await.unwrap();
+    let mut dl = AsyncDownload::new(&fdroid_jar_url, dir.path(), JAR_LOCAL_FILE).get().await
+        .map_err(|_| "Could not download F-Droid package repository.".to_string())?;
     let length = dl.length();
     let cb = match length {
-        Some(length) => Some(progress_wrapper(mp)(jar_local_file.to_string(), length)),
+        Some(length) => Some(progress_wrapper(mp)(JAR_LOCAL_FILE.to_string(), length)),
         None => None,
     };
     match dl.download(&cb).await {
         Ok(_) => {
             mp_log.suspend(|| println!("Package repository downloaded successfully!\nExtracting..."));
-            let file = fs::File::open(dir.path().join(jar_local_file)).unwrap();
-            match zip::ZipArchive::new(file) {
-                Ok(mut archive) => {
-                    for i in 0..archive.len() {
-                        let mut file = archive.by_index(i).unwrap();
-                        let outpath = match file.enclosed_name() {
-                            Some(path) => dir.path().join(path.to_owned()),
-                            None => continue,
-                        };
-                        if (&*file.name()).ends_with('/') {
-                            fs::create_dir_all(&outpath).unwrap();
-                        } else {
-                            if let Some(p) = outpath.parent() {
-                                if !p.exists() {
-                                    fs::create_dir_all(&p).unwrap();
-                                }
-                            }
-                            files.push(file.enclosed_name().unwrap().to_owned().into_os_string().into_string().unwrap());
-                            let mut outfile = fs::File::create(&outpath).unwrap();
-                            io::copy(&mut file, &mut outfile).unwrap();
-                        }
-
-                        // Get and Set permissions
-                        #[cfg(unix)]
-                        {
-                            use std::os::unix::fs::PermissionsExt;
+            extract_jar(dir, &dir.path().join(JAR_LOCAL_FILE))
+                .map_err(|_| "F-Droid package repository could not be extracted. Please try again.".to_string())
+        }
+        Err(_) => Err("Could not download F-Droid package repository.".to_string()),
+    }
+}
 
-                            if let Some(mode) = file.unix_mode() {
-                                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode)).unwrap();
-                            }
-                        }
-                    }
-                },
-                Err(_) => {
-                    print_error("F-Droid package repository could not be extracted. Please try again.", output_format);
-                    std::process::exit(1);
+/// Extracts a jar into `dir`, returning the names of the files in it.
+fn extract_jar(dir: &TempDir, jar: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut files = vec![];
+    let mut archive = zip::ZipArchive::new(fs::File::open(jar)?)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let outpath = match file.enclosed_name() {
+            Some(path) => dir.path().join(path.to_owned()),
+            None => continue,
+        };
+        if (&*file.name()).ends_with('/') {
+            fs::create_dir_all(&outpath)?;
+        } else {
+            if let Some(p) = outpath.parent() {
+                if !p.exists() {
+                    fs::create_dir_all(&p)?;
                 }
             }
+            files.push(file.enclosed_name().unwrap().to_owned().into_os_string().into_string().unwrap());
+            let mut outfile = fs::File::create(&outpath)?;
+            io::copy(&mut file, &mut outfile)?;
         }
-        Err(_) => {
-            print_error("Could not download F-Droid package repository.", output_format);
-            std::process::exit(1);
+
+        // Get and Set permissions
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if let Some(mode) = file.unix_mode() {
+                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+            }
         }
     }
-    files
+    Ok(files)
 }
@@ -0,0 +1,509 @@
+use std::collections::{hash_map::Entry, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use base64::{Engine as _, engine::general_purpose as b64_general_purpose};
+use cryptographic_message_syntax::{CmsError, SignedData};
+use regex::Regex;
+use ring::digest::{self, Context, SHA256, SHA512};
+use ring::signature::{self, VerificationAlgorithm};
+use sha1::{Sha1, Digest as Sha1Digest};
+use simple_error::SimpleError;
+use x509_certificate::X509CertificateError;
+use x509_certificate::certificate::CapturedX509Certificate;
+
+use crate::consts;
+
+const EOCD_MAGIC: u32 = 0x06054b50;
+const EOCD_MIN_SIZE: u64 = 22;
+const APK_SIG_BLOCK_MAGIC: &[u8] = b"APK Sig Block 42";
+const APK_SIGNATURE_SCHEME_V2_BLOCK_ID: u32 = 0x7109871a;
+const APK_SIGNATURE_SCHEME_V3_BLOCK_ID: u32 = 0xf05368c0;
+const CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// The signers of an APK whose signature was checked.
+pub enum ApkSigners {
+    /// The hex-encoded sha256 fingerprints of the certificates which signed the APK, in the same
+    /// form F-Droid uses for the `signer` field of its package index
+    Verified(Vec<String>),
+    /// The APK is signed, but with an algorithm which cannot be verified here, as described
+    Unsupported(String),
+}
+
+/// Verifies the signature of the APK at `path`, and returns the certificates which signed it.
+///
+/// APK Signature Scheme v3 and v2 blocks are preferred, in that order, and the content digests
+/// they cover are verified along with their signatures.  Only if neither is present do we fall
+/// back to the v1 (JAR) signature, in which case the manifest digests of every entry are checked.
+/// A v3 block signed only with unsupported algorithms falls back to the v2 block, if there is one.
+/// An APK with a signature which does not verify is an error, but one which is only signed with
+/// unsupported algorithms is not, since that says nothing about whether it was tampered with.
+pub fn verify_signers(path: &Path) -> Result<ApkSigners, Box<dyn Error>> {
+    let mut apk = File::open(path)?;
+    let (eocd_offset, eocd) = read_eocd(&mut apk)?;
+    let central_directory_offset = u64::from(read_u32(&eocd[16..20]));
+    let Some((signing_block_offset, pairs)) = read_signing_block(&mut apk, central_directory_offset)? else {
+        return verify_jar_signature(path);
+    };
+    let sections = ContentSections {
+        signing_block_offset,
+        central_directory_offset,
+        eocd_offset,
+        eocd,
+    };
+    match (pairs.get(&APK_SIGNATURE_SCHEME_V3_BLOCK_ID), pairs.get(&APK_SIGNATURE_SCHEME_V2_BLOCK_ID)) {
+        (Some(v3_block), v2_block) => match (verify_scheme_block(&mut apk, v3_block, true, &sections)?, v2_block) {
+            (ApkSigners::Unsupported(_), Some(v2_block)) => verify_scheme_block(&mut apk, v2_block, false, &sections),
+            (signers, _) => Ok(signers),
+        },
+        (None, Some(v2_block)) => verify_scheme_block(&mut apk, v2_block, false, &sections),
+        (None, None) => verify_jar_signature(path),
+    }
+}
+
+struct ContentSections {
+    signing_block_offset: u64,
+    central_directory_offset: u64,
+    eocd_offset: u64,
+    eocd: Vec<u8>,
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&bytes[0..8]);
+    u64::from_le_bytes(buf)
+}
+
+fn read_eocd(apk: &mut File) -> Result<(u64, Vec<u8>), Box<dyn Error>> {
+    let file_len = apk.metadata()?.len();
+    if file_len < EOCD_MIN_SIZE {
+        return Err(Box::new(SimpleError::new("APK is too small to be a valid ZIP archive.")));
+    }
+    // The EOCD record is followed by a comment of at most 65535 bytes
+    let search_len = std::cmp::min(file_len, EOCD_MIN_SIZE + 0xffff);
+    let search_start = file_len - search_len;
+    let mut buf = vec![0; search_len as usize];
+    apk.seek(SeekFrom::Start(search_start))?;
+    apk.read_exact(&mut buf)?;
+    for i in (0..=(buf.len() - EOCD_MIN_SIZE as usize)).rev() {
+        if read_u32(&buf[i..]) == EOCD_MAGIC {
+            let comment_len = u16::from_le_bytes([buf[i + 20], buf[i + 21]]) as usize;
+            if i + EOCD_MIN_SIZE as usize + comment_len == buf.len() {
+                return Ok((search_start + i as u64, buf[i..].to_vec()));
+            }
+        }
+    }
+    Err(Box::new(SimpleError::new("Could not find the end of central directory record of the APK.")))
+}
+
+type SigningBlock = (u64, HashMap<u32, Vec<u8>>);
+/// Returns the offset of the APK Signing Block and its ID-value pairs, if the APK has one.
+fn read_signing_block(apk: &mut File, central_directory_offset: u64) -> Result<Option<SigningBlock>, Box<dyn Error>> {
+    if central_directory_offset < 32 {
+        return Ok(None);
+    }
+    let mut footer = [0; 24];
+    apk.seek(SeekFrom::Start(central_directory_offset - 24))?;
+    apk.read_exact(&mut footer)?;
+    if &footer[8..24] != APK_SIG_BLOCK_MAGIC {
+        return Ok(None);
+    }
+    let block_size = read_u64(&footer[0..8]);
+    // The size is read from the APK, so the arithmetic on it must not overflow
+    let signing_block_offset = match block_size.checked_add(8).and_then(|total_size| central_directory_offset.checked_sub(total_size)) {
+        Some(signing_block_offset) if block_size >= 24 => signing_block_offset,
+        _ => return Err(Box::new(SimpleError::new("APK Signing Block has an invalid size."))),
+    };
+    let mut block = vec![0; usize::try_from(central_directory_offset - signing_block_offset)?];
+    apk.seek(SeekFrom::Start(signing_block_offset))?;
+    apk.read_exact(&mut block)?;
+    if read_u64(&block[0..8]) != block_size {
+        return Err(Box::new(SimpleError::new("APK Signing Block sizes do not match.")));
+    }
+
+    let mut pairs = HashMap::new();
+    let mut pairs_data = &block[8..block.len() - 24];
+    while !pairs_data.is_empty() {
+        if pairs_data.len() < 12 {
+            return Err(Box::new(SimpleError::new("APK Signing Block contains a truncated entry.")));
+        }
+        let pair_len = read_u64(pairs_data) as usize;
+        if pair_len < 4 || pair_len > pairs_data.len() - 8 {
+            return Err(Box::new(SimpleError::new("APK Signing Block contains an entry with an invalid size.")));
+        }
+        let id = read_u32(&pairs_data[8..12]);
+        pairs.insert(id, pairs_data[12..8 + pair_len].to_vec());
+        pairs_data = &pairs_data[8 + pair_len..];
+    }
+    Ok(Some((signing_block_offset, pairs)))
+}
+
+/// Hashes the certificate exactly as it was encoded in the APK, which is what F-Droid
+/// fingerprints, rather than re-encoding it.
+fn certificate_sha256(cert: &CapturedX509Certificate) -> String {
+    let mut context = Context::new(&SHA256);
+    context.update(cert.constructed_data());
+    hex::encode(context.finish().as_ref())
+}
+
+/// Splits off a `u32` length-prefixed slice from the front of `data`.
+fn take_length_prefixed<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], Box<dyn Error>> {
+    if data.len() < 4 {
+        return Err(Box::new(SimpleError::new("APK signature data is truncated.")));
+    }
+    let len = read_u32(data) as usize;
+    if len > data.len() - 4 {
+        return Err(Box::new(SimpleError::new("APK signature data has an invalid length prefix.")));
+    }
+    let (value, rest) = data[4..].split_at(len);
+    *data = rest;
+    Ok(value)
+}
+
+fn take_u32(data: &mut &[u8]) -> Result<u32, Box<dyn Error>> {
+    if data.len() < 4 {
+        return Err(Box::new(SimpleError::new("APK signature data is truncated.")));
+    }
+    let value = read_u32(data);
+    *data = &data[4..];
+    Ok(value)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ContentDigest {
+    ChunkedSha256,
+    ChunkedSha512,
+}
+
+/// Maps APK signature algorithm IDs to the verification algorithm and content digest they use.
+/// Algorithms which `ring` cannot verify (DSA, ECDSA with SHA-512 and the verity variants) are
+/// omitted, so signers relying on them alone are reported as unsupported.
+fn signature_algorithm(id: u32) -> Option<(&'static dyn VerificationAlgorithm, ContentDigest, u8)> {
+    match id {
+        0x0101 => Some((&signature::RSA_PSS_2048_8192_SHA256, ContentDigest::ChunkedSha256, 1)),
+        0x0102 => Some((&signature::RSA_PSS_2048_8192_SHA512, ContentDigest::ChunkedSha512, 2)),
+        0x0103 => Some((&signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY, ContentDigest::ChunkedSha256, 1)),
+        0x0104 => Some((&signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY, ContentDigest::ChunkedSha512, 2)),
+        0x0201 => Some((&signature::ECDSA_P256_SHA256_ASN1, ContentDigest::ChunkedSha256, 1)),
+        _ => None,
+    }
+}
+
+fn verify_scheme_block(apk: &mut File, block: &[u8], is_v3: bool, sections: &ContentSections) -> Result<ApkSigners, Box<dyn Error>> {
+    let scheme = if is_v3 { "v3" } else { "v2" };
+    let mut signers = block;
+    let mut signers = take_length_prefixed(&mut signers)?;
+    let mut computed_digests: HashMap<u8, Vec<u8>> = HashMap::new();
+    let mut fingerprints = vec![];
+    while !signers.is_empty() {
+        let mut signer = take_length_prefixed(&mut signers)?;
+        let signed_data = take_length_prefixed(&mut signer)?;
+        if is_v3 {
+            take_u32(&mut signer)?;
+            take_u32(&mut signer)?;
+        }
+        let mut signatures = take_length_prefixed(&mut signer)?;
+        let public_key = take_length_prefixed(&mut signer)?;
+
+        let mut best_signature = None;
+        let mut unsupported_ids = vec![];
+        while !signatures.is_empty() {
+            let mut signature = take_length_prefixed(&mut signatures)?;
+            let algorithm_id = take_u32(&mut signature)?;
+            let signature_bytes = take_length_prefixed(&mut signature)?;
+            match signature_algorithm(algorithm_id) {
+                Some((algorithm, content_digest, strength)) => match best_signature {
+                    Some((_, _, _, _, best_strength)) if best_strength >= strength => {},
+                    _ => best_signature = Some((algorithm_id, algorithm, content_digest, signature_bytes, strength)),
+                },
+                None => unsupported_ids.push(format!("{:#06x}", algorithm_id)),
+            }
+        }
+        let Some((algorithm_id, algorithm, content_digest, signature_bytes, _)) = best_signature else {
+            if unsupported_ids.is_empty() {
+                return Err(Box::new(SimpleError::new(format!("No signatures found in the APK Signature Scheme {} block.", scheme))));
+            }
+            return Ok(ApkSigners::Unsupported(format!("its APK Signature Scheme {} block only uses unsupported signature algorithms ({})", scheme, unsupported_ids.join(", "))));
+        };
+
+        let mut signed_data_fields = signed_data;
+        let mut digests = take_length_prefixed(&mut signed_data_fields)?;
+        let mut certificates = take_length_prefixed(&mut signed_data_fields)?;
+        let first_certificate = take_length_prefixed(&mut certificates)?;
+        let cert = CapturedX509Certificate::from_der(first_certificate.to_vec())?;
+        if !public_key.ends_with(&cert.public_key_data()) {
+            return Err(Box::new(SimpleError::new(format!("Public key does not match the certificate in the APK Signature Scheme {} block.", scheme))));
+        }
+        signature::UnparsedPublicKey::new(algorithm, cert.public_key_data())
+            .verify(signed_data, signature_bytes)
+            .map_err(|_| SimpleError::new(format!("APK Signature Scheme {} signature did not verify.", scheme)))?;
+
+        let mut signed_digest = None;
+        while !digests.is_empty() {
+            let mut digest = take_length_prefixed(&mut digests)?;
+            if take_u32(&mut digest)? == algorithm_id {
+                signed_digest = Some(take_length_prefixed(&mut digest)?);
+            }
+        }
+        let signed_digest = signed_digest.ok_or_else(|| {
+            SimpleError::new(format!("No content digest found for the APK Signature Scheme {} signature.", scheme))
+        })?;
+        let computed_digest = match computed_digests.entry(content_digest as u8) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(compute_content_digest(apk, content_digest, sections)?),
+        };
+        if computed_digest != signed_digest {
+            return Err(Box::new(SimpleError::new(format!("APK contents do not match the digest signed in the APK Signature Scheme {} block.", scheme))));
+        }
+        fingerprints.push(certificate_sha256(&cert));
+    }
+    if fingerprints.is_empty() {
+        return Err(Box::new(SimpleError::new(format!("No signers found in the APK Signature Scheme {} block.", scheme))));
+    }
+    Ok(ApkSigners::Verified(fingerprints))
+}
+
+/// Computes the chunked digest over the ZIP entries, central directory and end of central
+/// directory record, as specified by APK Signature Scheme v2.
+fn compute_content_digest(apk: &mut File, content_digest: ContentDigest, sections: &ContentSections) -> Result<Vec<u8>, Box<dyn Error>> {
+    let algorithm: &'static digest::Algorithm = match content_digest {
+        ContentDigest::ChunkedSha256 => &SHA256,
+        ContentDigest::ChunkedSha512 => &SHA512,
+    };
+    // The EOCD is digested as if the central directory immediately followed the ZIP entries
+    let mut eocd = sections.eocd.clone();
+    eocd[16..20].copy_from_slice(&(sections.signing_block_offset as u32).to_le_bytes());
+
+    let section_ranges = [
+        (0, sections.signing_block_offset),
+        (sections.central_directory_offset, sections.eocd_offset),
+    ];
+    let mut chunk_digests = vec![];
+    let mut chunk_count: u32 = 0;
+    let mut buf = vec![0; CHUNK_SIZE as usize];
+    let mut digest_chunk = |chunk: &[u8]| {
+        let mut context = Context::new(algorithm);
+        context.update(&[0xa5]);
+        context.update(&(chunk.len() as u32).to_le_bytes());
+        context.update(chunk);
+        chunk_digests.extend_from_slice(context.finish().as_ref());
+        chunk_count += 1;
+    };
+    for (start, end) in section_ranges {
+        apk.seek(SeekFrom::Start(start))?;
+        let mut position = start;
+        while position < end {
+            let len = std::cmp::min(CHUNK_SIZE, end - position) as usize;
+            apk.read_exact(&mut buf[..len])?;
+            digest_chunk(&buf[..len]);
+            position += len as u64;
+        }
+    }
+    for chunk in eocd.chunks(CHUNK_SIZE as usize) {
+        digest_chunk(chunk);
+    }
+
+    let mut context = Context::new(algorithm);
+    context.update(&[0x5a]);
+    context.update(&chunk_count.to_le_bytes());
+    context.update(&chunk_digests);
+    Ok(context.finish().as_ref().to_vec())
+}
+
+/// Parses a JAR manifest or signature file into its main attributes and per-entry sections.
+fn parse_manifest(data: &str) -> (HashMap<String, String>, HashMap<String, HashMap<String, String>>) {
+    let mut main = HashMap::new();
+    let mut entries = HashMap::new();
+    let mut current: HashMap<String, String> = HashMap::new();
+    let mut in_main = true;
+    let mut flush = |current: &mut HashMap<String, String>, in_main: &mut bool| {
+        if *in_main {
+            main = std::mem::take(current);
+            *in_main = false;
+        } else if let Some(name) = current.get("Name").cloned() {
+            entries.insert(name, std::mem::take(current));
+        } else {
+            current.clear();
+        }
+    };
+    let mut lines: Vec<String> = vec![];
+    for line in data.split("\r\n").flat_map(|l| l.split('\n')) {
+        match line.strip_prefix(' ') {
+            Some(continuation) if !lines.is_empty() => lines.last_mut().unwrap().push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    for line in lines {
+        if line.is_empty() {
+            if in_main || !current.is_empty() {
+                flush(&mut current, &mut in_main);
+            }
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(": ") {
+            current.insert(key.to_string(), value.to_string());
+        }
+    }
+    if in_main || !current.is_empty() {
+        flush(&mut current, &mut in_main);
+    }
+    (main, entries)
+}
+
+fn digest_for_attributes(attributes: &HashMap<String, String>, suffix: &str, data: &[u8]) -> Option<bool> {
+    if let Some(expected) = attributes.get(&format!("SHA-256{}", suffix)) {
+        let mut context = Context::new(&SHA256);
+        context.update(data);
+        return Some(b64_general_purpose::STANDARD.decode(expected).ok()? == context.finish().as_ref());
+    }
+    if let Some(expected) = attributes.get(&format!("SHA1{}", suffix)) {
+        let mut hasher = Sha1::new();
+        hasher.update(data);
+        return Some(b64_general_purpose::STANDARD.decode(expected).ok()?[..] == hasher.finalize()[..]);
+    }
+    None
+}
+
+/// Whether a CMS error is due to an algorithm which cannot be verified, rather than a signature
+/// which does not verify.
+fn is_unsupported_algorithm(err: &CmsError) -> bool {
+    matches!(err,
+        CmsError::UnknownKeyAlgorithm(_)
+        | CmsError::UnknownDigestAlgorithm(_)
+        | CmsError::UnknownSignatureAlgorithm(_)
+        | CmsError::X509Certificate(
+            X509CertificateError::UnknownDigestAlgorithm(_)
+            | X509CertificateError::UnknownSignatureAlgorithm(_)
+            | X509CertificateError::UnknownKeyAlgorithm(_)
+            | X509CertificateError::UnknownEllipticCurve(_)
+            | X509CertificateError::UnsupportedSignatureVerification(..)))
+}
+
+fn verify_jar_signature(path: &Path) -> Result<ApkSigners, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let re = Regex::new(consts::FDROID_SIGNATURE_BLOCK_FILE_REGEX).unwrap();
+    let cert_files: Vec<String> = archive.file_names().filter(|name| re.is_match(name)).map(String::from).collect();
+    if cert_files.is_empty() {
+        return Err(Box::new(SimpleError::new("APK is not signed.")));
+    }
+
+    let read_entry = |archive: &mut zip::ZipArchive<File>, name: &str| -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut data = vec![];
+        archive.by_name(name)?.read_to_end(&mut data)?;
+        Ok(data)
+    };
+    let manifest_data = read_entry(&mut archive, "META-INF/MANIFEST.MF")?;
+    let (_, manifest_entries) = parse_manifest(std::str::from_utf8(&manifest_data)?);
+
+    let mut fingerprints = vec![];
+    for cert_file in &cert_files {
+        let signed_file = match cert_file.rsplit_once('.') {
+            Some((stem, _)) => format!("{}.SF", stem),
+            None => continue,
+        };
+        let signed_content = read_entry(&mut archive, &signed_file)?;
+        let unsupported = |err: &CmsError| ApkSigners::Unsupported(format!("its v1 signature {} uses an unsupported algorithm ({})", cert_file, err));
+        let signed_data = match SignedData::parse_ber(&read_entry(&mut archive, cert_file)?) {
+            Ok(signed_data) => signed_data,
+            Err(err) if is_unsupported_algorithm(&err) => return Ok(unsupported(&err)),
+            Err(err) => return Err(Box::new(err)),
+        };
+        let signer_info = signed_data.signers().next()
+            .ok_or_else(|| SimpleError::new("No signatories provided in the APK signature block."))?;
+        // jarsigner includes signed attributes, in which case the signature covers those and
+        // they in turn carry the digest of the signed file
+        let signed_message = signer_info.signed_content(Some(&signed_content));
+        match signer_info.verify_signature_with_signed_data_and_content(&signed_data, &signed_message) {
+            Ok(()) => {},
+            Err(err) if is_unsupported_algorithm(&err) => return Ok(unsupported(&err)),
+            Err(err) => return Err(Box::new(err)),
+        }
+        if signer_info.signed_attributes().is_some() {
+            signer_info.verify_message_digest_with_content(&signed_content)?;
+        }
+        let cert = signed_data.certificates().next()
+            .ok_or_else(|| SimpleError::new("No certificate provided in the APK signature block."))?;
+
+        let (signed_main, _) = parse_manifest(std::str::from_utf8(&signed_content)?);
+        if digest_for_attributes(&signed_main, "-Digest-Manifest", &manifest_data) != Some(true) {
+            return Err(Box::new(SimpleError::new("The manifest digest from the APK signed file does not match the actual manifest.")));
+        }
+        fingerprints.push(certificate_sha256(cert));
+    }
+
+    let entry_names: Vec<String> = archive.file_names().map(String::from).collect();
+    for name in entry_names {
+        if name.ends_with('/') || (name.starts_with("META-INF/") && !name[9..].contains('/')) {
+            continue;
+        }
+        let attributes = manifest_entries.get(&name)
+            .ok_or_else(|| SimpleError::new(format!("APK entry {} is not covered by the signed manifest.", name)))?;
+        let data = read_entry(&mut archive, &name)?;
+        if digest_for_attributes(attributes, "-Digest", &data) != Some(true) {
+            return Err(Box::new(SimpleError::new(format!("APK entry {} does not match the digest in the signed manifest.", name))));
+        }
+    }
+    Ok(ApkSigners::Verified(fingerprints))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/fdroid").join(name)
+    }
+
+    fn verified(name: &str) -> Vec<String> {
+        match verify_signers(&fixture(name)).unwrap() {
+            ApkSigners::Verified(signers) => signers,
+            ApkSigners::Unsupported(reason) => panic!("{} was reported as unsupported: {}", name, reason),
+        }
+    }
+
+    fn unsupported(name: &str) -> String {
+        match verify_signers(&fixture(name)).unwrap() {
+            ApkSigners::Verified(signers) => panic!("{} was verified as signed by {:?}", name, signers),
+            ApkSigners::Unsupported(reason) => reason,
+        }
+    }
+
+    #[test]
+    fn verifies_v1_signature() {
+        assert_eq!(verified("v1-rsa.apk"), vec!["0892a4d948b0d0da15f215c7b1d45bf8339ed2b4d79634f302e6d8ab98fc596c"]);
+    }
+
+    #[test]
+    fn verifies_v2_signatures() {
+        assert_eq!(verified("v2-rsa.apk"), vec!["49b7d067ee6dd9ebfe57138a6540ef157b70ebdf041441e5711043313ec8f257"]);
+        assert_eq!(verified("v2-ec.apk"), vec!["3364a16e33de26a051e07e386d8a76b57765812f344c00cf019bc2f996851f11"]);
+    }
+
+    #[test]
+    fn prefers_v3_signature() {
+        assert_eq!(verified("v3-rsa-v2-ec.apk"), vec!["160beeb9aaf2a8094fb97764a1a33a61e7d04ffc920234e05396c9283904bc7f"]);
+    }
+
+    #[test]
+    fn falls_back_to_v2_when_v3_is_unsupported() {
+        assert_eq!(verified("v3-dsa-v2-rsa.apk"), vec!["cc855bb6c5b9944bbaa6db98caacad89a6145a4be4962668f72fb65672af1268"]);
+    }
+
+    #[test]
+    fn reports_unsupported_algorithms() {
+        assert!(unsupported("v2-dsa.apk").contains("0x0301"));
+        assert!(unsupported("v1-dsa.apk").contains("META-INF/DSA.DSA"));
+    }
+
+    #[test]
+    fn rejects_tampered_apks() {
+        assert!(verify_signers(&fixture("v1-tampered.apk")).is_err());
+        assert!(verify_signers(&fixture("v2-tampered.apk")).is_err());
+    }
+}
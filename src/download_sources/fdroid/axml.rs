@@ -0,0 +1,301 @@
+use std::error::Error;
+use std::io::Read;
+use std::path::Path;
+
+use simple_error::SimpleError;
+
+const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_XML_TYPE: u16 = 0x0003;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+const RES_XML_RESOURCE_MAP_TYPE: u16 = 0x0180;
+
+const UTF8_FLAG: u32 = 1 << 8;
+
+const TYPE_STRING: u8 = 0x03;
+const TYPE_INT_DEC: u8 = 0x10;
+const TYPE_INT_HEX: u8 = 0x11;
+
+// Attribute resource IDs, used when the attribute names have been stripped by an obfuscator
+const ATTR_NAME: u32 = 0x01010003;
+const ATTR_VERSION_CODE: u32 = 0x0101021b;
+const ATTR_VERSION_NAME: u32 = 0x0101021c;
+const ATTR_MIN_SDK_VERSION: u32 = 0x0101020c;
+const ATTR_TARGET_SDK_VERSION: u32 = 0x01010270;
+
+/// The parts of an APK's `AndroidManifest.xml` which are needed for an F-Droid index.
+#[derive(Default)]
+pub struct Manifest {
+    pub package_name: String,
+    pub version_code: u64,
+    pub version_name: Option<String>,
+    pub min_sdk: Option<u64>,
+    pub target_sdk: Option<u64>,
+    pub permissions: Vec<String>,
+    /// ABIs which the APK contains native code for, from its `lib/` directories
+    pub nativecode: Vec<String>,
+}
+
+impl Manifest {
+    pub fn from_apk(apk: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(apk)?)?;
+        let mut manifest = {
+            let mut data = vec![];
+            archive.by_name("AndroidManifest.xml")?.read_to_end(&mut data)?;
+            Self::parse(&data)?
+        };
+        for name in archive.file_names() {
+            if let Some(abi) = name.strip_prefix("lib/").and_then(|path| path.split_once('/')).map(|(abi, _)| abi) {
+                if !abi.is_empty() && !manifest.nativecode.iter().any(|existing| existing == abi) {
+                    manifest.nativecode.push(abi.to_string());
+                }
+            }
+        }
+        manifest.nativecode.sort();
+        Ok(manifest)
+    }
+
+    /// Parses Android's binary XML format, which is what `AndroidManifest.xml` is compiled to.
+    fn parse(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if read_u16(data, 0)? != RES_XML_TYPE {
+            return Err(Box::new(SimpleError::new("AndroidManifest.xml is not in binary XML format.")));
+        }
+        let mut strings: Vec<String> = vec![];
+        let mut resource_ids: Vec<u32> = vec![];
+        let mut manifest = Manifest::default();
+        let mut found_manifest = false;
+
+        let mut offset = read_u16(data, 2)? as usize;
+        while offset + 8 <= data.len() {
+            let chunk_type = read_u16(data, offset)?;
+            let header_size = read_u16(data, offset + 2)? as usize;
+            let chunk_size = read_u32(data, offset + 4)? as usize;
+            if chunk_size < 8 || offset + chunk_size > data.len() {
+                return Err(Box::new(SimpleError::new("AndroidManifest.xml contains a malformed chunk.")));
+            }
+            let chunk = &data[offset..offset + chunk_size];
+            match chunk_type {
+                RES_STRING_POOL_TYPE => strings = parse_string_pool(chunk)?,
+                RES_XML_RESOURCE_MAP_TYPE => {
+                    resource_ids = chunk.get(header_size..).ok_or_else(truncated)?.chunks_exact(4)
+                        .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+                        .collect();
+                },
+                RES_XML_START_ELEMENT_TYPE => {
+                    let element = Element::parse(chunk, header_size, &strings, &resource_ids)?;
+                    match element.name.as_str() {
+                        "manifest" => {
+                            found_manifest = true;
+                            manifest.package_name = element.string("package", None).unwrap_or_default();
+                            manifest.version_code = element.int("versionCode", Some(ATTR_VERSION_CODE)).unwrap_or(0);
+                            manifest.version_name = element.string("versionName", Some(ATTR_VERSION_NAME));
+                        },
+                        "uses-sdk" => {
+                            manifest.min_sdk = element.int("minSdkVersion", Some(ATTR_MIN_SDK_VERSION));
+                            manifest.target_sdk = element.int("targetSdkVersion", Some(ATTR_TARGET_SDK_VERSION));
+                        },
+                        "uses-permission" | "uses-permission-sdk-23" => {
+                            if let Some(permission) = element.string("name", Some(ATTR_NAME)) {
+                                manifest.permissions.push(permission);
+                            }
+                        },
+                        _ => {},
+                    }
+                },
+                _ => {},
+            }
+            offset += chunk_size;
+        }
+
+        if !found_manifest || manifest.package_name.is_empty() {
+            return Err(Box::new(SimpleError::new("AndroidManifest.xml does not specify a package name.")));
+        }
+        Ok(manifest)
+    }
+}
+
+struct Attribute {
+    name: String,
+    resource_id: Option<u32>,
+    raw_value: Option<String>,
+    data_type: u8,
+    data: u32,
+}
+
+struct Element {
+    name: String,
+    attributes: Vec<Attribute>,
+}
+
+impl Element {
+    fn parse(chunk: &[u8], header_size: usize, strings: &[String], resource_ids: &[u32]) -> Result<Self, Box<dyn Error>> {
+        let string_at = |index: u32| strings.get(index as usize).cloned();
+        let name = string_at(read_u32(chunk, header_size + 4)?).unwrap_or_default();
+        let attribute_start = read_u16(chunk, header_size + 8)? as usize;
+        let attribute_size = read_u16(chunk, header_size + 10)? as usize;
+        let attribute_count = read_u16(chunk, header_size + 12)? as usize;
+        let mut attributes = vec![];
+        for i in 0..attribute_count {
+            let attribute_offset = header_size + attribute_start + i * attribute_size;
+            let name_index = read_u32(chunk, attribute_offset + 4)?;
+            attributes.push(Attribute {
+                name: string_at(name_index).unwrap_or_default(),
+                resource_id: resource_ids.get(name_index as usize).copied(),
+                raw_value: string_at(read_u32(chunk, attribute_offset + 8)?),
+                data_type: *chunk.get(attribute_offset + 15).ok_or_else(truncated)?,
+                data: read_u32(chunk, attribute_offset + 16)?,
+            });
+        }
+        Ok(Element { name, attributes })
+    }
+
+    fn attribute(&self, name: &str, resource_id: Option<u32>) -> Option<&Attribute> {
+        self.attributes.iter().find(|attribute| attribute.name == name)
+            .or_else(|| resource_id.and_then(|resource_id| {
+                self.attributes.iter().find(|attribute| attribute.resource_id == Some(resource_id))
+            }))
+    }
+
+    fn string(&self, name: &str, resource_id: Option<u32>) -> Option<String> {
+        let attribute = self.attribute(name, resource_id)?;
+        match attribute.data_type {
+            TYPE_INT_DEC | TYPE_INT_HEX => Some(attribute.data.to_string()),
+            _ => attribute.raw_value.clone(),
+        }
+    }
+
+    fn int(&self, name: &str, resource_id: Option<u32>) -> Option<u64> {
+        let attribute = self.attribute(name, resource_id)?;
+        match attribute.data_type {
+            TYPE_INT_DEC | TYPE_INT_HEX => Some(attribute.data as u64),
+            TYPE_STRING => attribute.raw_value.as_ref().and_then(|value| value.parse().ok()),
+            _ => None,
+        }
+    }
+}
+
+fn parse_string_pool(chunk: &[u8]) -> Result<Vec<String>, Box<dyn Error>> {
+    let string_count = read_u32(chunk, 8)? as usize;
+    let flags = read_u32(chunk, 16)?;
+    let strings_start = read_u32(chunk, 20)? as usize;
+    let header_size = read_u16(chunk, 2)? as usize;
+    // The count is read from the APK, so its table of offsets must fit in the chunk before any
+    // room is made for the strings
+    let offsets_end = string_count.checked_mul(4).and_then(|offsets_size| offsets_size.checked_add(header_size));
+    if !matches!(offsets_end, Some(offsets_end) if offsets_end <= chunk.len()) {
+        return Err(truncated());
+    }
+    let mut strings = Vec::with_capacity(string_count);
+    for i in 0..string_count {
+        let mut offset = strings_start + read_u32(chunk, header_size + i * 4)? as usize;
+        let string = if flags & UTF8_FLAG != 0 {
+            // The UTF-16 length precedes the UTF-8 length in bytes
+            let (_, size) = read_utf8_length(chunk, offset)?;
+            offset += size;
+            let (length, size) = read_utf8_length(chunk, offset)?;
+            offset += size;
+            let bytes = chunk.get(offset..offset + length).ok_or_else(truncated)?;
+            String::from_utf8_lossy(bytes).to_string()
+        } else {
+            let mut length = read_u16(chunk, offset)? as usize;
+            offset += 2;
+            if length & 0x8000 != 0 {
+                length = ((length & 0x7fff) << 16) | read_u16(chunk, offset)? as usize;
+                offset += 2;
+            }
+            let units: Vec<u16> = (0..length)
+                .map(|unit| read_u16(chunk, offset + unit * 2))
+                .collect::<Result<_, _>>()?;
+            String::from_utf16_lossy(&units)
+        };
+        strings.push(string);
+    }
+    Ok(strings)
+}
+
+/// Lengths in a UTF-8 string pool take one byte, or two if the high bit of the first is set.
+fn read_utf8_length(data: &[u8], offset: usize) -> Result<(usize, usize), Box<dyn Error>> {
+    let first = *data.get(offset).ok_or_else(truncated)? as usize;
+    if first & 0x80 != 0 {
+        let second = *data.get(offset + 1).ok_or_else(truncated)? as usize;
+        Ok((((first & 0x7f) << 8) | second, 2))
+    } else {
+        Ok((first, 1))
+    }
+}
+
+fn truncated() -> Box<dyn Error> {
+    Box::new(SimpleError::new("AndroidManifest.xml is truncated."))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Box<dyn Error>> {
+    let bytes = data.get(offset..offset + 2).ok_or_else(truncated)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Box<dyn Error>> {
+    let bytes = data.get(offset..offset + 4).ok_or_else(truncated)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/fdroid").join(name)).unwrap()
+    }
+
+    fn assert_example_manifest(manifest: &Manifest) {
+        assert_eq!(manifest.package_name, "org.example.app");
+        assert_eq!(manifest.version_code, 42);
+        assert_eq!(manifest.version_name.as_deref(), Some("1.2"));
+        assert_eq!(manifest.min_sdk, Some(21));
+        assert_eq!(manifest.target_sdk, Some(34));
+        assert_eq!(manifest.permissions, vec!["android.permission.INTERNET", "android.permission.CAMERA"]);
+    }
+
+    #[test]
+    fn parses_utf16_manifest() {
+        assert_example_manifest(&Manifest::parse(&fixture("manifest-utf16.axml")).unwrap());
+    }
+
+    #[test]
+    fn parses_utf8_manifest() {
+        assert_example_manifest(&Manifest::parse(&fixture("manifest-utf8.axml")).unwrap());
+    }
+
+    #[test]
+    fn parses_manifest_with_stripped_attribute_names() {
+        assert_example_manifest(&Manifest::parse(&fixture("manifest-obfuscated.axml")).unwrap());
+    }
+
+    #[test]
+    fn rejects_text_manifest() {
+        assert!(Manifest::parse(b"<manifest package=\"org.example.app\" />").is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_manifest() {
+        let data = fixture("manifest-utf16.axml");
+        for length in [0, 1, 8, 20, 100, data.len() - 1] {
+            assert!(Manifest::parse(&data[..length]).is_err(), "accepted the first {} bytes", length);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_chunks() {
+        let data = fixture("manifest-utf16.axml");
+        // The string pool is the first chunk, after the 8 byte header of the document
+        let mut excess_strings = data.clone();
+        excess_strings[16..20].copy_from_slice(&0x7fffffffu32.to_le_bytes());
+        assert!(Manifest::parse(&excess_strings).is_err());
+
+        let mut oversized_chunk = data.clone();
+        oversized_chunk[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Manifest::parse(&oversized_chunk).is_err());
+
+        let mut undersized_chunk = data;
+        undersized_chunk[12..16].copy_from_slice(&4u32.to_le_bytes());
+        assert!(Manifest::parse(&undersized_chunk).is_err());
+    }
+}
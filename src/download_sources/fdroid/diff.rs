@@ -0,0 +1,147 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::{json, Value};
+
+use super::index::{Index, Package};
+
+/// What changed between two snapshots of a package index.
+pub struct IndexDiff {
+    pub added_packages: Vec<String>,
+    pub removed_packages: Vec<String>,
+    /// Packages in both snapshots which changed, sorted by package ID
+    pub changed_packages: Vec<PackageDiff>,
+}
+
+/// A version, identified by its version code, since several APKs may share a version name.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VersionId {
+    pub version_code: u64,
+    pub version_name: Option<String>,
+}
+
+#[derive(Default)]
+pub struct PackageDiff {
+    pub package_id: String,
+    pub added_versions: Vec<VersionId>,
+    pub removed_versions: Vec<VersionId>,
+    /// Signers across every version of the package, as lowercase hex sha256 fingerprints
+    pub added_signers: Vec<String>,
+    pub removed_signers: Vec<String>,
+    pub added_anti_features: Vec<String>,
+    pub removed_anti_features: Vec<String>,
+    /// The old and new license, if it changed
+    pub license: Option<(Option<String>, Option<String>)>,
+}
+
+impl IndexDiff {
+    pub fn between(old: &Index, new: &Index) -> Self {
+        let added_packages = new.packages.keys()
+            .filter(|package_id| !old.packages.contains_key(*package_id))
+            .cloned()
+            .collect();
+        let removed_packages = old.packages.keys()
+            .filter(|package_id| !new.packages.contains_key(*package_id))
+            .cloned()
+            .collect();
+        let changed_packages = old.packages.iter()
+            .filter_map(|(package_id, old_package)| {
+                let new_package = new.packages.get(package_id)?;
+                let package_diff = PackageDiff::between(package_id, old_package, new_package);
+                (!package_diff.is_empty()).then_some(package_diff)
+            })
+            .collect();
+        IndexDiff { added_packages, removed_packages, changed_packages }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_packages.is_empty() && self.removed_packages.is_empty() && self.changed_packages.is_empty()
+    }
+
+    pub fn to_json(&self) -> Value {
+        let changed: serde_json::Map<String, Value> = self.changed_packages.iter()
+            .map(|package_diff| (package_diff.package_id.to_string(), package_diff.to_json()))
+            .collect();
+        json!({
+            "added_packages": self.added_packages,
+            "removed_packages": self.removed_packages,
+            "changed_packages": changed,
+        })
+    }
+}
+
+impl PackageDiff {
+    fn between(package_id: &str, old: &Package, new: &Package) -> Self {
+        let old_versions = versions(old);
+        let new_versions = versions(new);
+        let (added_signers, removed_signers) = added_and_removed(&signers(old), &signers(new));
+        let (added_anti_features, removed_anti_features) = added_and_removed(
+            &old.anti_features.iter().cloned().collect(),
+            &new.anti_features.iter().cloned().collect(),
+        );
+        PackageDiff {
+            package_id: package_id.to_string(),
+            added_versions: new_versions.iter()
+                .filter(|(version_code, _)| !old_versions.contains_key(*version_code))
+                .map(|(version_code, version_name)| VersionId { version_code: *version_code, version_name: version_name.clone() })
+                .collect(),
+            removed_versions: old_versions.iter()
+                .filter(|(version_code, _)| !new_versions.contains_key(*version_code))
+                .map(|(version_code, version_name)| VersionId { version_code: *version_code, version_name: version_name.clone() })
+                .collect(),
+            added_signers,
+            removed_signers,
+            added_anti_features,
+            removed_anti_features,
+            license: (old.license != new.license).then(|| (old.license.clone(), new.license.clone())),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added_versions.is_empty() && self.removed_versions.is_empty()
+            && self.added_signers.is_empty() && self.removed_signers.is_empty()
+            && self.added_anti_features.is_empty() && self.removed_anti_features.is_empty()
+            && self.license.is_none()
+    }
+
+    fn to_json(&self) -> Value {
+        let versions_json = |versions: &[VersionId]| -> Vec<Value> {
+            versions.iter()
+                .map(|version| json!({"version": version.version_name, "version_code": version.version_code}))
+                .collect()
+        };
+        let mut package_json = serde_json::Map::new();
+        let mut insert_if_not_empty = |key: &str, value: Value| {
+            if value.as_array().is_some_and(|array| !array.is_empty()) {
+                package_json.insert(key.to_string(), value);
+            }
+        };
+        insert_if_not_empty("added_versions", json!(versions_json(&self.added_versions)));
+        insert_if_not_empty("removed_versions", json!(versions_json(&self.removed_versions)));
+        insert_if_not_empty("added_signers", json!(self.added_signers));
+        insert_if_not_empty("removed_signers", json!(self.removed_signers));
+        insert_if_not_empty("added_anti_features", json!(self.added_anti_features));
+        insert_if_not_empty("removed_anti_features", json!(self.removed_anti_features));
+        if let Some((old_license, new_license)) = &self.license {
+            package_json.insert("license".to_string(), json!({"old": old_license, "new": new_license}));
+        }
+        Value::Object(package_json)
+    }
+}
+
+/// The versions of a package by version code.  APKs which share a version code, such as the
+/// differently signed builds of a reproducible version, are counted once.
+fn versions(package: &Package) -> BTreeMap<u64, Option<String>> {
+    package.versions.iter()
+        .filter_map(|version| version.version_code.map(|version_code| (version_code, version.version_name.clone())))
+        .collect()
+}
+
+fn signers(package: &Package) -> BTreeSet<String> {
+    package.versions.iter()
+        .flat_map(|version| version.signers.iter().cloned())
+        .collect()
+}
+
+fn added_and_removed(old: &BTreeSet<String>, new: &BTreeSet<String>) -> (Vec<String>, Vec<String>) {
+    (new.difference(old).cloned().collect(), old.difference(new).cloned().collect())
+}
@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use super::error::Error as FDroidError;
+
+/// A typed view of an F-Droid package index, which normalizes the differences between
+/// `index-v1.json` and the index referenced by `entry.json` (currently version `20002`).
+///
+/// Like the rest of the index parsing, this is deliberately lenient: fields which are missing or
+/// of an unexpected type are left empty rather than failing the whole index.
+pub struct Index {
+    pub repo_address: String,
+    pub packages: BTreeMap<String, Package>,
+}
+
+#[derive(Default)]
+pub struct Package {
+    pub name: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub categories: Vec<String>,
+    pub anti_features: Vec<String>,
+    pub license: Option<String>,
+    pub author_name: Option<String>,
+    /// Milliseconds since the epoch
+    pub last_updated: Option<u64>,
+    /// The version code the repository recommends installing, which may be lower than the latest
+    pub suggested_version_code: Option<u64>,
+    /// In the order they are listed in the index
+    pub versions: Vec<PackageVersion>,
+    /// Localized names, summaries and descriptions.  Only index-v2 lists these for every locale.
+    pub localized_texts: Vec<LocalizedText>,
+    /// Icons, feature graphics and screenshots.  Only index-v2 lists their sha256, so they are not
+    /// read from index-v1.
+    pub graphics: Vec<Graphic>,
+}
+
+#[derive(Clone)]
+pub struct LocalizedText {
+    pub locale: String,
+    /// `name`, `summary` or `description`
+    pub field: String,
+    pub text: String,
+}
+
+#[derive(Clone)]
+pub struct Graphic {
+    pub locale: String,
+    /// The metadata key for single images, such as `icon` or `featureGraphic`, or for screenshots
+    /// the directory F-Droid keeps them in, such as `phoneScreenshots`
+    pub kind: String,
+    pub is_screenshot: bool,
+    /// The path of the file within the repository
+    pub name: String,
+    pub sha256: Option<String>,
+}
+
+#[derive(Default)]
+pub struct PackageVersion {
+    pub version_name: Option<String>,
+    pub version_code: Option<u64>,
+    pub filename: String,
+    pub sha256: String,
+    /// Lowercase hex sha256 fingerprints of the signing certificates, sorted and deduplicated
+    pub signers: Vec<String>,
+    pub nativecode: Vec<String>,
+    pub min_sdk: Option<u64>,
+    pub max_sdk: Option<u64>,
+    /// The size of the APK in bytes
+    pub size: Option<u64>,
+    /// When the version was added to the repository, in milliseconds since the epoch
+    pub added: Option<u64>,
+    /// For index-v1, this includes the anti-features of the app as a whole
+    pub anti_features: Vec<String>,
+    /// Empty for stable releases
+    pub release_channels: Vec<String>,
+    pub source: Option<SourceFile>,
+}
+
+/// The source tarball an APK was built from.  index-v1 only gives its name, while index-v2 also
+/// gives its sha256.
+#[derive(Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub sha256: Option<String>,
+}
+
+impl Index {
+    pub fn from_value(index: &Value) -> Result<Self, FDroidError> {
+        let index_map = index.as_object().ok_or(FDroidError::Dummy)?;
+        let repo = index_map.get("repo").ok_or(FDroidError::Dummy)?;
+        let repo_address = repo
+            .get("address").ok_or(FDroidError::Dummy)?
+            .as_str().ok_or(FDroidError::Dummy)?
+            .to_string();
+
+        let packages_map = index_map
+            .get("packages").ok_or(FDroidError::Dummy)?
+            .as_object().ok_or(FDroidError::Dummy)?;
+
+        // index-v1 keeps app metadata in a separate `apps` array
+        let mut v1_apps: BTreeMap<&str, &Map<String, Value>> = BTreeMap::new();
+        if let Some(Value::Array(apps)) = index_map.get("apps") {
+            for app in apps {
+                if let Some(app) = app.as_object() {
+                    if let Some(Value::String(package_name)) = app.get("packageName") {
+                        v1_apps.insert(package_name, app);
+                    }
+                }
+            }
+        }
+
+        let mut packages = BTreeMap::new();
+        for (package_name, package_value) in packages_map {
+            let package = match package_value {
+                Value::Array(versions) => Package::from_v1(v1_apps.get(package_name.as_str()).copied(), versions),
+                Value::Object(package) => Package::from_v2(package),
+                _ => continue,
+            };
+            packages.insert(package_name.to_string(), package);
+        }
+        Ok(Index { repo_address, packages })
+    }
+}
+
+impl Package {
+    fn from_v1(app: Option<&Map<String, Value>>, versions: &[Value]) -> Self {
+        let mut package = Package {
+            versions: versions.iter()
+                .filter_map(Value::as_object)
+                .map(PackageVersion::from_v1)
+                .collect(),
+            ..Default::default()
+        };
+        if let Some(app) = app {
+            let localized = app.get("localized").and_then(Value::as_object);
+            let localized_string = |key: &str| {
+                app.get(key).and_then(Value::as_str).map(String::from).or_else(|| {
+                    localized.and_then(|localized| {
+                        let mut by_locale = Map::new();
+                        for (locale, strings) in localized {
+                            if let Some(value) = strings.get(key) {
+                                by_locale.insert(locale.to_string(), value.clone());
+                            }
+                        }
+                        preferred_locale_string(&by_locale)
+                    })
+                })
+            };
+            package.name = localized_string("name");
+            package.summary = localized_string("summary");
+            package.description = localized_string("description");
+            package.categories = string_array(app.get("categories"));
+            package.anti_features = string_array(app.get("antiFeatures"));
+            package.license = app.get("license").and_then(Value::as_str).map(String::from);
+            package.author_name = app.get("authorName").and_then(Value::as_str).map(String::from);
+            package.last_updated = app.get("lastUpdated").and_then(Value::as_u64);
+            package.suggested_version_code = lenient_u64(app.get("suggestedVersionCode"));
+            // index-v1 mostly lists anti-features for the app, rather than for each version
+            for version in package.versions.iter_mut() {
+                version.anti_features.extend(package.anti_features.iter().cloned());
+                version.anti_features.sort();
+                version.anti_features.dedup();
+            }
+        }
+        package
+    }
+
+    fn from_v2(package: &Map<String, Value>) -> Self {
+        let metadata = package.get("metadata").and_then(Value::as_object);
+        let localized = |key: &str| {
+            match metadata.and_then(|metadata| metadata.get(key)) {
+                Some(Value::Object(by_locale)) => preferred_locale_string(by_locale),
+                Some(Value::String(value)) => Some(value.to_string()),
+                _ => None,
+            }
+        };
+        let versions: Vec<PackageVersion> = match package.get("versions") {
+            Some(Value::Object(versions)) => versions.values()
+                .filter_map(Value::as_object)
+                .map(PackageVersion::from_v2)
+                .collect(),
+            _ => vec![],
+        };
+        // index-v2 only lists anti-features per version, so the package is considered to have
+        // any anti-feature one of its versions has
+        let mut anti_features: Vec<String> = versions.iter()
+            .flat_map(|version| version.anti_features.iter().cloned())
+            .collect();
+        anti_features.sort();
+        anti_features.dedup();
+        Package {
+            name: localized("name"),
+            summary: localized("summary"),
+            description: localized("description"),
+            categories: string_array(metadata.and_then(|metadata| metadata.get("categories"))),
+            anti_features,
+            license: metadata.and_then(|metadata| metadata.get("license")).and_then(Value::as_str).map(String::from),
+            author_name: metadata.and_then(|metadata| metadata.get("authorName")).and_then(Value::as_str).map(String::from),
+            last_updated: metadata.and_then(|metadata| metadata.get("lastUpdated")).and_then(Value::as_u64),
+            suggested_version_code: lenient_u64(metadata.and_then(|metadata| metadata.get("suggestedVersionCode"))),
+            versions,
+            localized_texts: metadata.map_or(vec![], localized_texts_from_v2),
+            graphics: metadata.map_or(vec![], graphics_from_v2),
+        }
+    }
+
+    /// The version with the highest version code.
+    pub fn latest_version(&self) -> Option<&PackageVersion> {
+        self.versions.iter().max_by_key(|version| version.version_code.unwrap_or(0))
+    }
+}
+
+impl PackageVersion {
+    fn from_v1(version: &Map<String, Value>) -> Self {
+        PackageVersion {
+            version_name: version.get("versionName").and_then(Value::as_str).map(String::from),
+            version_code: lenient_u64(version.get("versionCode")),
+            filename: version.get("apkName").and_then(Value::as_str).unwrap_or_default().to_string(),
+            sha256: version.get("hash").and_then(Value::as_str).unwrap_or_default().to_string(),
+            signers: signers_from_value(version.get("signer")),
+            nativecode: string_array(version.get("nativecode")),
+            min_sdk: lenient_u64(version.get("minSdkVersion")),
+            max_sdk: lenient_u64(version.get("maxSdkVersion")),
+            size: lenient_u64(version.get("size")),
+            added: lenient_u64(version.get("added")),
+            anti_features: string_array(version.get("antiFeatures")),
+            release_channels: vec![],
+            source: version.get("srcname").and_then(Value::as_str).map(|name| SourceFile {
+                name: name.to_string(),
+                sha256: None,
+            }),
+        }
+    }
+
+    fn from_v2(version: &Map<String, Value>) -> Self {
+        let manifest = version.get("manifest").and_then(Value::as_object);
+        let manifest_value = |key: &str| manifest.and_then(|manifest| manifest.get(key));
+        let file = version.get("file").and_then(Value::as_object);
+        let file_value = |key: &str| file.and_then(|file| file.get(key));
+        let anti_features = match version.get("antiFeatures") {
+            Some(Value::Object(anti_features)) => anti_features.keys().cloned().collect(),
+            other => string_array(other),
+        };
+        PackageVersion {
+            version_name: manifest_value("versionName").and_then(Value::as_str).map(String::from),
+            version_code: lenient_u64(manifest_value("versionCode")),
+            filename: file_value("name").and_then(Value::as_str).unwrap_or_default().trim_start_matches('/').to_string(),
+            sha256: file_value("sha256").and_then(Value::as_str).unwrap_or_default().to_string(),
+            signers: signers_from_value(manifest_value("signer")),
+            nativecode: string_array(manifest_value("nativecode")),
+            min_sdk: lenient_u64(manifest_value("usesSdk").and_then(|uses_sdk| uses_sdk.get("minSdkVersion"))),
+            max_sdk: lenient_u64(manifest_value("maxSdkVersion")),
+            size: lenient_u64(file_value("size")),
+            added: lenient_u64(version.get("added")),
+            anti_features,
+            release_channels: string_array(version.get("releaseChannels")),
+            source: version.get("src").and_then(Value::as_object).and_then(|src| {
+                src.get("name").and_then(Value::as_str).map(|name| SourceFile {
+                    name: name.trim_start_matches('/').to_string(),
+                    sha256: src.get("sha256").and_then(Value::as_str).map(String::from),
+                })
+            }),
+        }
+    }
+}
+
+fn localized_texts_from_v2(metadata: &Map<String, Value>) -> Vec<LocalizedText> {
+    let mut texts = vec![];
+    for field in ["name", "summary", "description"] {
+        if let Some(Value::Object(by_locale)) = metadata.get(field) {
+            for (locale, text) in by_locale {
+                if let Some(text) = text.as_str() {
+                    texts.push(LocalizedText { locale: locale.to_string(), field: field.to_string(), text: text.to_string() });
+                }
+            }
+        }
+    }
+    texts
+}
+
+/// index-v2 lists each image as a file object (`name`, `sha256` and `size`) by locale, and each
+/// set of screenshots as an array of them.
+/// The screenshot device types F-Droid defines, each of which names a directory
+const SCREENSHOT_DEVICES: &[&str] = &["phone", "sevenInch", "tenInch", "tv", "wear"];
+
+fn graphics_from_v2(metadata: &Map<String, Value>) -> Vec<Graphic> {
+    let graphic = |locale: &str, kind: &str, is_screenshot: bool, file: &Value| {
+        let name = file.get("name").and_then(Value::as_str)?.trim_start_matches('/');
+        Some(Graphic {
+            locale: locale.to_string(),
+            kind: kind.to_string(),
+            is_screenshot,
+            name: name.to_string(),
+            sha256: file.get("sha256").and_then(Value::as_str).map(String::from),
+        })
+    };
+    let mut graphics = vec![];
+    for kind in ["icon", "featureGraphic", "promoGraphic", "tvBanner"] {
+        if let Some(Value::Object(by_locale)) = metadata.get(kind) {
+            graphics.extend(by_locale.iter().filter_map(|(locale, file)| graphic(locale, kind, false, file)));
+        }
+    }
+    if let Some(Value::Object(screenshots)) = metadata.get("screenshots") {
+        for (device, by_locale) in screenshots {
+            if !SCREENSHOT_DEVICES.contains(&device.as_str()) {
+                continue;
+            }
+            let kind = format!("{}Screenshots", device);
+            if let Value::Object(by_locale) = by_locale {
+                for (locale, files) in by_locale {
+                    if let Value::Array(files) = files {
+                        graphics.extend(files.iter().filter_map(|file| graphic(locale, &kind, true, file)));
+                    }
+                }
+            }
+        }
+    }
+    graphics
+}
+
+/// F-Droid's index-v1 lists a single `signer` per APK, while newer indexes list an array of
+/// fingerprints under `manifest.signer.sha256`.
+fn signers_from_value(signer: Option<&Value>) -> Vec<String> {
+    let mut signers: Vec<String> = match signer {
+        Some(Value::String(signer)) => vec![signer.to_lowercase()],
+        Some(Value::Object(signer)) => match signer.get("sha256") {
+            Some(Value::Array(sha256s)) => sha256s.iter()
+                .filter_map(|sha256| sha256.as_str().map(|sha256| sha256.to_lowercase()))
+                .collect(),
+            _ => vec![],
+        },
+        _ => vec![],
+    };
+    signers.sort();
+    signers.dedup();
+    signers
+}
+
+fn string_array(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(values)) => values.iter().filter_map(|value| value.as_str().map(String::from)).collect(),
+        _ => vec![],
+    }
+}
+
+/// Some numeric fields, such as index-v1's `versionCode` in older repositories, are serialized as
+/// strings.
+fn lenient_u64(value: Option<&Value>) -> Option<u64> {
+    match value {
+        Some(Value::Number(number)) => number.as_u64(),
+        Some(Value::String(string)) => string.parse().ok(),
+        _ => None,
+    }
+}
+
+fn preferred_locale_string(by_locale: &Map<String, Value>) -> Option<String> {
+    ["en-US", "en"].iter()
+        .find_map(|locale| by_locale.get(*locale))
+        .or_else(|| by_locale.values().next())
+        .and_then(Value::as_str)
+        .map(String::from)
+}
@@ -0,0 +1,473 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use base64::{Engine as _, engine::general_purpose as b64_general_purpose};
+use bcder::{encode::Values, Mode, Oid};
+use cryptographic_message_syntax::{SignedDataBuilder, SignerBuilder};
+use cryptographic_message_syntax::asn1::rfc5652::{SignatureValue, OID_ID_DATA};
+use ring::digest::{digest, SHA256};
+use serde_json::{json, Map, Value};
+use sha1::{Sha1, Digest as Sha1Digest};
+use sha2::Sha256;
+use simple_error::SimpleError;
+use x509_certificate::{EcdsaCurve, KeyAlgorithm, InMemorySigningKeyPair, Sign, Signer, X509CertificateBuilder};
+use x509_certificate::certificate::CapturedX509Certificate;
+
+use super::apk_signature::{self, ApkSigners};
+use super::axml::Manifest;
+use crate::config;
+
+const INDEX_V2_VERSION: u64 = 20002;
+const INDEX_V1_VERSION: u64 = 21;
+const SIGNATURE_FILE_BASENAME: &str = "META-INF/APKEEP";
+
+/// An APK which has been read and verified, ready to be listed in the index.
+struct RepoApk {
+    filename: String,
+    manifest: Manifest,
+    sha256: String,
+    size: u64,
+    signers: Vec<String>,
+    /// Milliseconds since the epoch, taken from the file's modification time
+    added: u64,
+}
+
+pub struct RepoSummary {
+    pub packages: usize,
+    pub apks: usize,
+    pub skipped: Vec<(String, String)>,
+    /// Hex-encoded sha256 fingerprint of the certificate the index was signed with
+    pub fingerprint: String,
+    /// Where the signing key was generated, if a new one had to be created
+    pub generated_key: Option<PathBuf>,
+}
+
+/// Builds an F-Droid repository out of the APKs in `repo_dir`, writing `index-v1.json`,
+/// `index-v2.json` and `entry.json` alongside them, as well as `index-v1.jar` and `entry.jar`
+/// signed by the repository key.  The result can be served as-is, with `repo_dir` at the URL
+/// given by the `repo_address` option.
+pub fn build(repo_dir: &Path, options: &HashMap<&str, &str>, log: impl Fn(String)) -> Result<RepoSummary, Box<dyn Error>> {
+    let address = options.get("repo_address")
+        .ok_or_else(|| SimpleError::new("The repo_address option must be given, as the URL the repository will be served from"))?
+        .trim_end_matches('/');
+    let name = options.get("repo_name").copied().unwrap_or("apkeep");
+    let description = options.get("repo_description").copied().unwrap_or("A repository of APKs collected with apkeep");
+    let (key, cert, generated_key) = signing_key(options, name)?;
+
+    let mut apk_files: Vec<PathBuf> = fs::read_dir(repo_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == "apk"))
+        .collect();
+    apk_files.sort();
+
+    let mut packages: BTreeMap<String, Vec<RepoApk>> = BTreeMap::new();
+    let mut skipped = vec![];
+    for apk_file in apk_files {
+        let filename = apk_file.file_name().unwrap().to_string_lossy().to_string();
+        match read_apk(&apk_file, &filename, &log) {
+            Ok(apk) => {
+                log(format!("Adding {} version {} from {}", apk.manifest.package_name, apk.manifest.version_code, filename));
+                packages.entry(apk.manifest.package_name.clone()).or_default().push(apk);
+            },
+            Err(err) => {
+                log(format!("Could not add {} ({}). Skipping...", filename, err));
+                skipped.push((filename, err.to_string()));
+            },
+        }
+    }
+    for apks in packages.values_mut() {
+        apks.sort_by_key(|apk| std::cmp::Reverse(apk.manifest.version_code));
+    }
+
+    let timestamp = chrono::Utc::now().timestamp_millis() as u64;
+    let index_v1 = index_v1_json(&packages, address, name, description, timestamp);
+    let index_v2 = index_v2_json(&packages, address, name, description, timestamp);
+    let index_v2_data = serde_json::to_vec(&index_v2)?;
+    let entry = json!({
+        "timestamp": timestamp,
+        "version": INDEX_V2_VERSION,
+        "index": {
+            "name": "/index-v2.json",
+            "sha256": hex::encode(digest(&SHA256, &index_v2_data)),
+            "size": index_v2_data.len(),
+            "numPackages": packages.len(),
+        },
+        "diffs": {},
+    });
+    let index_v1_data = serde_json::to_vec(&index_v1)?;
+    let entry_data = serde_json::to_vec(&entry)?;
+
+    fs::write(repo_dir.join("index-v2.json"), &index_v2_data)?;
+    fs::write(repo_dir.join("index-v1.json"), &index_v1_data)?;
+    fs::write(repo_dir.join("entry.json"), &entry_data)?;
+    write_signed_jar(&repo_dir.join("index-v1.jar"), "index-v1.json", &index_v1_data, false, &key, &cert)?;
+    write_signed_jar(&repo_dir.join("entry.jar"), "entry.json", &entry_data, true, &key, &cert)?;
+
+    Ok(RepoSummary {
+        packages: packages.len(),
+        apks: packages.values().map(Vec::len).sum(),
+        skipped,
+        fingerprint: hex::encode(digest(&SHA256, cert.constructed_data())),
+        generated_key,
+    })
+}
+
+fn read_apk(path: &Path, filename: &str, log: &impl Fn(String)) -> Result<RepoApk, Box<dyn Error>> {
+    let manifest = Manifest::from_apk(path)?;
+    // An APK whose signature cannot be checked is listed without a signer, which clients take to
+    // mean it is only verified by its sha256sum
+    let mut signers = match apk_signature::verify_signers(path)? {
+        ApkSigners::Verified(signers) => signers,
+        ApkSigners::Unsupported(reason) => {
+            log(format!("The signer of {} is not listed, since {}.", filename, reason));
+            vec![]
+        },
+    };
+    signers.sort();
+    signers.dedup();
+    let data = fs::read(path)?;
+    let metadata = fs::metadata(path)?;
+    let added = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_millis() as u64;
+    Ok(RepoApk {
+        filename: filename.to_string(),
+        manifest,
+        sha256: hex::encode(digest(&SHA256, &data)),
+        size: metadata.len(),
+        signers,
+        added,
+    })
+}
+
+fn index_v1_json(packages: &BTreeMap<String, Vec<RepoApk>>, address: &str, name: &str, description: &str, timestamp: u64) -> Value {
+    let apps: Vec<Value> = packages.iter().map(|(package_name, apks)| {
+        json!({
+            "packageName": package_name,
+            "name": package_name,
+            "license": "Unknown",
+            "suggestedVersionCode": apks[0].manifest.version_code.to_string(),
+            "added": apks.iter().map(|apk| apk.added).min(),
+            "lastUpdated": apks.iter().map(|apk| apk.added).max(),
+        })
+    }).collect();
+    let v1_packages: Map<String, Value> = packages.iter().map(|(package_name, apks)| {
+        let versions: Vec<Value> = apks.iter().map(|apk| {
+            let mut version = json!({
+                "packageName": package_name,
+                "versionCode": apk.manifest.version_code,
+                "versionName": apk.manifest.version_name,
+                "apkName": apk.filename,
+                "hash": apk.sha256,
+                "hashType": "sha256",
+                "size": apk.size,
+                "added": apk.added,
+                "minSdkVersion": apk.manifest.min_sdk,
+                "targetSdkVersion": apk.manifest.target_sdk,
+                "uses-permission": apk.manifest.permissions.iter().map(|permission| json!([permission, null])).collect::<Vec<Value>>(),
+            });
+            // index-v1 only has room for a single signer
+            if let Some(signer) = apk.signers.first() {
+                version["signer"] = json!(signer);
+            }
+            if !apk.manifest.nativecode.is_empty() {
+                version["nativecode"] = json!(apk.manifest.nativecode);
+            }
+            version
+        }).collect();
+        (package_name.to_string(), json!(versions))
+    }).collect();
+    json!({
+        "repo": {
+            "timestamp": timestamp,
+            "version": INDEX_V1_VERSION,
+            "name": name,
+            "address": address,
+            "description": description,
+        },
+        "requests": {"install": [], "uninstall": []},
+        "apps": apps,
+        "packages": v1_packages,
+    })
+}
+
+fn index_v2_json(packages: &BTreeMap<String, Vec<RepoApk>>, address: &str, name: &str, description: &str, timestamp: u64) -> Value {
+    let v2_packages: Map<String, Value> = packages.iter().map(|(package_name, apks)| {
+        let versions: Map<String, Value> = apks.iter().map(|apk| {
+            let mut manifest = json!({
+                "versionName": apk.manifest.version_name,
+                "versionCode": apk.manifest.version_code,
+                "usesSdk": {
+                    "minSdkVersion": apk.manifest.min_sdk.unwrap_or(1),
+                    "targetSdkVersion": apk.manifest.target_sdk.or(apk.manifest.min_sdk).unwrap_or(1),
+                },
+                "usesPermission": apk.manifest.permissions.iter().map(|permission| json!({"name": permission})).collect::<Vec<Value>>(),
+            });
+            if !apk.signers.is_empty() {
+                manifest["signer"] = json!({"sha256": apk.signers});
+            }
+            if !apk.manifest.nativecode.is_empty() {
+                manifest["nativecode"] = json!(apk.manifest.nativecode);
+            }
+            (apk.sha256.to_string(), json!({
+                "added": apk.added,
+                "file": {
+                    "name": format!("/{}", apk.filename),
+                    "sha256": apk.sha256,
+                    "size": apk.size,
+                },
+                "manifest": manifest,
+            }))
+        }).collect();
+        (package_name.to_string(), json!({
+            "metadata": {
+                "name": {"en-US": package_name},
+                "added": apks.iter().map(|apk| apk.added).min(),
+                "lastUpdated": apks.iter().map(|apk| apk.added).max(),
+            },
+            "versions": versions,
+        }))
+    }).collect();
+    json!({
+        "repo": {
+            "name": {"en-US": name},
+            "description": {"en-US": description},
+            "address": address,
+            "timestamp": timestamp,
+        },
+        "packages": v2_packages,
+    })
+}
+
+/// Loads the repository signing key from the `repo_key` and `repo_cert` options, both PEM files,
+/// or from the config directory.  If neither exists, a new ECDSA key and self-signed certificate
+/// are generated and stored in the config directory, so the repository fingerprint stays the same
+/// across runs.
+fn signing_key(options: &HashMap<&str, &str>, name: &str) -> Result<(InMemorySigningKeyPair, CapturedX509Certificate, Option<PathBuf>), Box<dyn Error>> {
+    let (key_file, cert_file) = match (options.get("repo_key"), options.get("repo_cert")) {
+        (Some(key_file), Some(cert_file)) => (PathBuf::from(key_file), PathBuf::from(cert_file)),
+        (None, None) => {
+            let mut key_dir = config::config_dir()?;
+            key_dir.push("fdroid-repo-key");
+            config::create_dir(&key_dir)?;
+            (key_dir.join("key.pem"), key_dir.join("cert.pem"))
+        },
+        _ => return Err(Box::new(SimpleError::new("The repo_key and repo_cert options must be given together"))),
+    };
+
+    if key_file.is_file() && cert_file.is_file() {
+        let key = InMemorySigningKeyPair::from_pkcs8_pem(fs::read(&key_file)?)?;
+        let cert = CapturedX509Certificate::from_pem(fs::read(&cert_file)?)?;
+        return Ok((key, cert, None));
+    }
+    if options.contains_key("repo_key") {
+        return Err(Box::new(SimpleError::new("The files given by the repo_key and repo_cert options could not be found")));
+    }
+
+    let mut builder = X509CertificateBuilder::default();
+    builder.subject().append_common_name_utf8_string(name)
+        .map_err(|_| SimpleError::new("Could not set the subject of the repository certificate"))?;
+    builder.validity_duration(chrono::Duration::days(365 * 10));
+    builder.constraint_not_ca();
+    let (cert, key) = builder.create_with_random_keypair(KeyAlgorithm::Ecdsa(EcdsaCurve::Secp256r1))?;
+    let key_pem = pem_encode("PRIVATE KEY", &key.to_pkcs8_one_asymmetric_key_der());
+    {
+        let mut key_fp = create_private_file(&key_file)?;
+        key_fp.write_all(key_pem.as_bytes())?;
+    }
+    fs::write(&cert_file, cert.encode_pem())?;
+    Ok((key, cert, Some(key_file)))
+}
+
+fn create_private_file(path: &Path) -> std::io::Result<File> {
+    let mut open_options = fs::OpenOptions::new();
+    open_options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        open_options.mode(0o600);
+    }
+    open_options.open(path)
+}
+
+fn pem_encode(label: &str, der: &[u8]) -> String {
+    let encoded = b64_general_purpose::STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+type JarDigest = (&'static str, fn(&[u8]) -> String);
+const JAR_SHA1: JarDigest = ("SHA1", |data| b64_general_purpose::STANDARD.encode(Sha1::digest(data)));
+const JAR_SHA256: JarDigest = ("SHA-256", |data| b64_general_purpose::STANDARD.encode(Sha256::digest(data)));
+
+/// Writes a signed JAR containing a single file, in the form F-Droid clients (and
+/// `verify_and_return_json`) expect: `entry.jar` uses SHA-256 digests, while `index-v1.jar` lists
+/// SHA1 digests first for older readers, followed by SHA-256 for those which have disabled SHA1.
+///
+/// The signature block has no signed attributes, so it signs the `.SF` file directly.
+fn write_signed_jar(jar: &Path, name: &str, contents: &[u8], use_sha256: bool, key: &InMemorySigningKeyPair, cert: &CapturedX509Certificate) -> Result<(), Box<dyn Error>> {
+    let digests: &[JarDigest] = if use_sha256 {
+        &[JAR_SHA256]
+    } else {
+        &[JAR_SHA1, JAR_SHA256]
+    };
+    let digest_lines = |suffix: &str, data: &[u8]| -> String {
+        digests.iter()
+            .map(|(digest_name, jar_digest)| format!("{}-Digest{}: {}\r\n", digest_name, suffix, jar_digest(data)))
+            .collect()
+    };
+    let entry_section = format!("Name: {}\r\n{}\r\n", name, digest_lines("", contents));
+    let manifest = format!("Manifest-Version: 1.0\r\nCreated-By: apkeep\r\n\r\n{}", entry_section);
+    let signature_file = format!(
+        "Signature-Version: 1.0\r\n{}Created-By: apkeep\r\n\r\nName: {}\r\n{}\r\n",
+        digest_lines("-Manifest", manifest.as_bytes()), name, digest_lines("", entry_section.as_bytes()));
+
+    let block_extension = match key.key_algorithm() {
+        Some(KeyAlgorithm::Rsa) => "RSA",
+        Some(KeyAlgorithm::Ecdsa(_)) => "EC",
+        _ => return Err(Box::new(SimpleError::new("The repository key must be an RSA or ECDSA key"))),
+    };
+    let signature_block = signature_block(signature_file.as_bytes(), key, cert)?;
+
+    let mut zip = zip::ZipWriter::new(File::create(jar)?);
+    let file_options = zip::write::SimpleFileOptions::default();
+    zip.start_file("META-INF/MANIFEST.MF", file_options)?;
+    zip.write_all(manifest.as_bytes())?;
+    zip.start_file(format!("{}.SF", SIGNATURE_FILE_BASENAME), file_options)?;
+    zip.write_all(signature_file.as_bytes())?;
+    zip.start_file(format!("{}.{}", SIGNATURE_FILE_BASENAME, block_extension), file_options)?;
+    zip.write_all(&signature_block)?;
+    zip.start_file(name, file_options)?;
+    zip.write_all(contents)?;
+    zip.finish()?;
+    Ok(())
+}
+
+fn signature_block(content: &[u8], key: &InMemorySigningKeyPair, cert: &CapturedX509Certificate) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut signed_data = SignedDataBuilder::default()
+        .content_external(content.to_vec())
+        .content_type(Oid(OID_ID_DATA.as_ref().to_vec().into()))
+        .signer(SignerBuilder::new(key, cert.clone()))
+        .build_signed_data()?;
+    let signature = key.try_sign(content)?;
+    for signer_info in signed_data.signer_infos.iter_mut() {
+        signer_info.signed_attributes = None;
+        signer_info.signature = SignatureValue::new(signature.as_ref().to_vec().into());
+    }
+    let mut der = vec![];
+    signed_data.encode_ref().write_encoded(Mode::Der, &mut der)?;
+    Ok(der)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1_RSA_SIGNER: &str = "0892a4d948b0d0da15f215c7b1d45bf8339ed2b4d79634f302e6d8ab98fc596c";
+
+    /// Copies APKs from the fixtures into a new repository directory, with a newly generated
+    /// repository key and certificate beside it.
+    fn repo_with(apks: &[&str]) -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo_dir = dir.path().join("repo");
+        fs::create_dir(&repo_dir).unwrap();
+        for apk in apks {
+            fs::copy(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/fdroid").join(apk), repo_dir.join(apk)).unwrap();
+        }
+        let (cert, key) = X509CertificateBuilder::default()
+            .create_with_random_keypair(KeyAlgorithm::Ecdsa(EcdsaCurve::Secp256r1))
+            .unwrap();
+        let key_file = dir.path().join("key.pem");
+        let cert_file = dir.path().join("cert.pem");
+        fs::write(&key_file, pem_encode("PRIVATE KEY", &key.to_pkcs8_one_asymmetric_key_der())).unwrap();
+        fs::write(&cert_file, cert.encode_pem()).unwrap();
+        (dir, key_file, cert_file)
+    }
+
+    fn build_repo(apks: &[&str]) -> (tempfile::TempDir, RepoSummary) {
+        let (dir, key_file, cert_file) = repo_with(apks);
+        let key_file = key_file.to_string_lossy().to_string();
+        let cert_file = cert_file.to_string_lossy().to_string();
+        let options = HashMap::from([
+            ("repo_address", "https://example.org/repo/"),
+            ("repo_key", key_file.as_str()),
+            ("repo_cert", cert_file.as_str()),
+        ]);
+        let summary = build(&dir.path().join("repo"), &options, |_| {}).unwrap();
+        (dir, summary)
+    }
+
+    fn read_json(path: &Path) -> Value {
+        serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn lists_apks_in_both_indexes() {
+        let (dir, summary) = build_repo(&["repo-v1-rsa.apk"]);
+        assert_eq!((summary.packages, summary.apks), (1, 1));
+        assert!(summary.skipped.is_empty());
+        assert!(summary.generated_key.is_none());
+        let repo_dir = dir.path().join("repo");
+
+        let index_v2 = read_json(&repo_dir.join("index-v2.json"));
+        assert_eq!(index_v2["repo"]["address"], "https://example.org/repo");
+        let versions = index_v2["packages"]["org.example.app"]["versions"].as_object().unwrap();
+        assert_eq!(versions.len(), 1);
+        let version = versions.values().next().unwrap();
+        assert_eq!(version["file"]["name"], "/repo-v1-rsa.apk");
+        assert_eq!(version["manifest"]["versionCode"], 42);
+        assert_eq!(version["manifest"]["versionName"], "1.2");
+        assert_eq!(version["manifest"]["usesSdk"], json!({"minSdkVersion": 21, "targetSdkVersion": 34}));
+        assert_eq!(version["manifest"]["nativecode"], json!(["arm64-v8a"]));
+        assert_eq!(version["manifest"]["signer"]["sha256"], json!([V1_RSA_SIGNER]));
+
+        let index_v1 = read_json(&repo_dir.join("index-v1.json"));
+        let package = &index_v1["packages"]["org.example.app"][0];
+        assert_eq!(package["apkName"], "repo-v1-rsa.apk");
+        assert_eq!(package["signer"], V1_RSA_SIGNER);
+        assert_eq!(package["uses-permission"], json!([["android.permission.INTERNET", null], ["android.permission.CAMERA", null]]));
+    }
+
+    #[test]
+    fn signs_indexes_with_repository_key() {
+        let (dir, summary) = build_repo(&["repo-v1-rsa.apk"]);
+        let repo_dir = dir.path().join("repo");
+        for jar in ["entry.jar", "index-v1.jar"] {
+            match apk_signature::verify_signers(&repo_dir.join(jar)).unwrap() {
+                ApkSigners::Verified(signers) => assert_eq!(signers, vec![summary.fingerprint.to_string()]),
+                ApkSigners::Unsupported(reason) => panic!("{} could not be verified: {}", jar, reason),
+            }
+        }
+        let entry = read_json(&repo_dir.join("entry.json"));
+        let index_v2_data = fs::read(repo_dir.join("index-v2.json")).unwrap();
+        assert_eq!(entry["index"]["sha256"], hex::encode(digest(&SHA256, &index_v2_data)));
+    }
+
+    #[test]
+    fn lists_apk_with_unsupported_signature_without_signer() {
+        let (dir, summary) = build_repo(&["repo-v2-dsa.apk"]);
+        assert_eq!(summary.apks, 1);
+        let index_v2 = read_json(&dir.path().join("repo/index-v2.json"));
+        let version = index_v2["packages"]["org.example.app"]["versions"].as_object().unwrap().values().next().unwrap();
+        assert!(version["manifest"].get("signer").is_none());
+        let index_v1 = read_json(&dir.path().join("repo/index-v1.json"));
+        assert!(index_v1["packages"]["org.example.app"][0].get("signer").is_none());
+    }
+
+    #[test]
+    fn skips_apks_which_cannot_be_read() {
+        // The signature fixtures have a placeholder in place of a compiled manifest, and the
+        // tampered one does not verify either
+        let (_dir, summary) = build_repo(&["repo-v1-rsa.apk", "v1-rsa.apk", "v1-tampered.apk"]);
+        assert_eq!(summary.apks, 1);
+        let skipped: Vec<&str> = summary.skipped.iter().map(|(filename, _)| filename.as_str()).collect();
+        assert_eq!(skipped, vec!["v1-rsa.apk", "v1-tampered.apk"]);
+    }
+}
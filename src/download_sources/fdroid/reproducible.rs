@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::consts;
+
+/// Compares the contents of two APKs, ignoring their v1 signature files.  APK Signature Scheme
+/// v2 and v3 blocks live outside the zip entries, so they are ignored as well.
+///
+/// Returns the names of entries which differ or are only present in one of the APKs, so an empty
+/// list means the APKs were built reproducibly.
+pub fn differing_entries(first: &Path, second: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let first_entries = unsigned_entries(first)?;
+    let second_entries = unsigned_entries(second)?;
+    let mut differences: Vec<String> = first_entries.iter()
+        .filter(|(name, contents)| second_entries.get(*name) != Some(contents))
+        .map(|(name, _)| name.to_string())
+        .collect();
+    differences.extend(second_entries.keys()
+        .filter(|name| !first_entries.contains_key(*name))
+        .cloned());
+    differences.sort();
+    Ok(differences)
+}
+
+fn unsigned_entries(apk: &Path) -> Result<BTreeMap<String, Vec<u8>>, Box<dyn Error>> {
+    let re = Regex::new(consts::APK_SIGNATURE_FILE_REGEX).unwrap();
+    let mut archive = zip::ZipArchive::new(File::open(apk)?)?;
+    let mut entries = BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() || re.is_match(file.name()) {
+            continue;
+        }
+        let mut contents = vec![];
+        file.read_to_end(&mut contents)?;
+        entries.insert(file.name().to_string(), contents);
+    }
+    Ok(entries)
+}
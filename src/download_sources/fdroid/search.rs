@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use super::index::Package;
+use crate::util::date::parse_date_ms;
+
+/// Criteria for selecting packages from an F-Droid index, built from the `-o` options.  Options
+/// taking multiple values separate them with a semicolon, as with `arch=`.
+#[derive(Default)]
+pub struct PackageFilter {
+    /// Lowercased terms which must all appear in the package ID, name, summary or description
+    terms: Vec<String>,
+    /// The package must be in at least one of these categories
+    categories: Vec<String>,
+    /// The package must have all of these anti-features
+    anti_features: Vec<String>,
+    /// The package must have none of these anti-features
+    excluded_anti_features: Vec<String>,
+    license: Option<String>,
+    author: Option<String>,
+    updated_after: Option<u64>,
+    updated_before: Option<u64>,
+    /// At least one version of the package must be installable at this SDK level
+    min_sdk: Option<u64>,
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(';')
+        .map(|item| item.trim().to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+impl PackageFilter {
+    pub fn from_options(query: Option<&str>, options: &HashMap<&str, &str>) -> Result<Self, String> {
+        let mut filter = PackageFilter {
+            terms: query.map_or(vec![], |query| query.split_whitespace().map(|term| term.to_lowercase()).collect()),
+            ..Default::default()
+        };
+        if let Some(categories) = options.get("category") {
+            filter.categories = split_list(categories);
+        }
+        if let Some(anti_features) = options.get("anti_feature") {
+            for anti_feature in split_list(anti_features) {
+                match anti_feature.strip_prefix('!') {
+                    Some(excluded) => filter.excluded_anti_features.push(excluded.to_string()),
+                    None => filter.anti_features.push(anti_feature),
+                }
+            }
+        }
+        filter.license = options.get("license").map(|license| license.to_lowercase());
+        filter.author = options.get("author").map(|author| author.to_lowercase());
+        let date_option = |key: &str| -> Result<Option<u64>, String> {
+            match options.get(key) {
+                Some(date) => parse_date_ms(date).map(Some)
+                    .ok_or_else(|| format!("Option {} must be a date in the form YYYY-MM-DD", key)),
+                None => Ok(None),
+            }
+        };
+        filter.updated_after = date_option("updated_after")?;
+        filter.updated_before = date_option("updated_before")?;
+        if let Some(min_sdk) = options.get("min_sdk") {
+            filter.min_sdk = Some(min_sdk.parse().map_err(|_| "Option min_sdk must be a number".to_string())?);
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, package_id: &str, package: &Package) -> bool {
+        let lowercase = |value: &Option<String>| value.as_deref().unwrap_or_default().to_lowercase();
+        if !self.terms.is_empty() {
+            let haystack = [
+                package_id.to_lowercase(),
+                lowercase(&package.name),
+                lowercase(&package.summary),
+                lowercase(&package.description),
+            ].join("\n");
+            if !self.terms.iter().all(|term| haystack.contains(term)) {
+                return false;
+            }
+        }
+        let has = |values: &[String], wanted: &str| values.iter().any(|value| value.to_lowercase() == wanted);
+        if !self.categories.is_empty() && !self.categories.iter().any(|category| has(&package.categories, category)) {
+            return false;
+        }
+        if !self.anti_features.iter().all(|anti_feature| has(&package.anti_features, anti_feature)) {
+            return false;
+        }
+        if self.excluded_anti_features.iter().any(|anti_feature| has(&package.anti_features, anti_feature)) {
+            return false;
+        }
+        if let Some(license) = &self.license {
+            if &lowercase(&package.license) != license {
+                return false;
+            }
+        }
+        if let Some(author) = &self.author {
+            if !lowercase(&package.author_name).contains(author.as_str()) {
+                return false;
+            }
+        }
+        if self.updated_after.is_some() || self.updated_before.is_some() {
+            let last_updated = match package.last_updated {
+                Some(last_updated) => last_updated,
+                None => return false,
+            };
+            if self.updated_after.is_some_and(|updated_after| last_updated < updated_after) ||
+               self.updated_before.is_some_and(|updated_before| last_updated >= updated_before) {
+                return false;
+            }
+        }
+        if let Some(min_sdk) = self.min_sdk {
+            if !package.versions.iter().any(|version| version.min_sdk.unwrap_or(1) <= min_sdk) {
+                return false;
+            }
+        }
+        true
+    }
+}
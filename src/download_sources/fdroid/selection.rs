@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::util::version::VersionSpec;
+
+use super::index::{Package, PackageVersion};
+
+/// Whether a version in the index is the one requested with `@version` or `@code:N`.
+pub fn version_matches(spec: &VersionSpec, version: &PackageVersion) -> bool {
+    match spec {
+        VersionSpec::Name(name) => version.version_name.as_ref() == Some(name),
+        VersionSpec::Code(code) => version.version_code == Some(*code),
+    }
+}
+
+/// Which release channels a version may be in to be selected, set with the `release_channel`
+/// option.  Versions which are not in any channel are stable releases.
+enum ReleaseChannels {
+    Stable,
+    /// Stable releases, as well as those in this (lowercased) channel
+    StableAnd(String),
+    All,
+}
+
+/// Rules for which versions of an app may be chosen when no version is specified.  These apply in
+/// the same way to `index-v1.json` and `index-v2.json`, and are applied before the `versions`
+/// option picks the newest of what remains.
+pub struct VersionPolicy {
+    release_channels: ReleaseChannels,
+    /// Never select a version newer than the package's `suggestedVersionCode`
+    suggested_only: bool,
+    /// Lowercased anti-features which rule a version out
+    excluded_anti_features: Vec<String>,
+    /// The SDK level of the device the versions are for
+    sdk: Option<u64>,
+}
+
+impl VersionPolicy {
+    pub fn from_options(options: &HashMap<&str, &str>) -> Result<Self, String> {
+        let release_channels = match options.get("release_channel").map(|val| val.to_lowercase()) {
+            None => ReleaseChannels::Stable,
+            Some(val) if val == "stable" => ReleaseChannels::Stable,
+            Some(val) if val == "all" => ReleaseChannels::All,
+            Some(val) => ReleaseChannels::StableAnd(val),
+        };
+        let suggested_only = match options.get("suggested") {
+            None => false,
+            Some(val) if val == &"1" || val.to_lowercase() == "true" => true,
+            Some(val) if val == &"0" || val.to_lowercase() == "false" => false,
+            Some(val) => return Err(format!("Invalid suggested option \"{}\", must be true or false", val)),
+        };
+        let excluded_anti_features = options.get("exclude_anti_features").map_or(vec![], |anti_features| {
+            anti_features.split(';')
+                .map(|anti_feature| anti_feature.trim().to_lowercase())
+                .filter(|anti_feature| !anti_feature.is_empty())
+                .collect()
+        });
+        let sdk = match options.get("sdk") {
+            Some(sdk) => Some(sdk.parse().map_err(|_| "Option sdk must be a number".to_string())?),
+            None => None,
+        };
+        Ok(VersionPolicy { release_channels, suggested_only, excluded_anti_features, sdk })
+    }
+
+    pub fn allows(&self, package: &Package, version: &PackageVersion) -> bool {
+        let in_channel = |wanted: &str| version.release_channels.iter().any(|channel| channel.to_lowercase() == wanted);
+        let channel_allowed = match &self.release_channels {
+            ReleaseChannels::Stable => version.release_channels.is_empty(),
+            ReleaseChannels::StableAnd(channel) => version.release_channels.is_empty() || in_channel(channel),
+            ReleaseChannels::All => true,
+        };
+        if !channel_allowed {
+            return false;
+        }
+        if self.suggested_only {
+            if let (Some(suggested), Some(version_code)) = (package.suggested_version_code, version.version_code) {
+                if version_code > suggested {
+                    return false;
+                }
+            }
+        }
+        if version.anti_features.iter().any(|anti_feature| self.excluded_anti_features.contains(&anti_feature.to_lowercase())) {
+            return false;
+        }
+        if let Some(sdk) = self.sdk {
+            if version.min_sdk.is_some_and(|min_sdk| min_sdk > sdk) || version.max_sdk.is_some_and(|max_sdk| max_sdk < sdk) {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -6,9 +6,10 @@ use futures_util::StreamExt;
 use gpapi::error::ErrorKind as GpapiErrorKind;
 use gpapi::Gpapi;
 use indicatif::MultiProgress;
+use serde_json::{json, Value};
 use tokio::time::{sleep, Duration as TokioDuration};
 
-use crate::util::progress_bar::progress_wrapper;
+use crate::util::{format_size, OutputFormat, progress_bar::progress_wrapper, version::VersionSpec};
 
 pub async fn download_apps(
     apps: Vec<(String, Option<String>)>,
@@ -33,6 +34,88 @@ pub async fn download_apps(
         Some(val) if val == "1" || val.to_lowercase() == "true" => true,
         _ => false,
     };
+    let gpa = login(email, aas_token, device, accept_tos, &mut options).await;
+
+    let mp = Rc::new(MultiProgress::new());
+    let gpa = Rc::new(gpa);
+    futures_util::stream::iter(
+        apps.into_iter().map(|app| {
+            let (app_id, app_version) = app;
+            let gpa = Rc::clone(&gpa);
+            let mp_dl1 = Rc::clone(&mp);
+            let mp_dl2 = Rc::clone(&mp);
+            let mp_dl3 = Rc::clone(&mp);
+            let mp_log = Rc::clone(&mp);
+
+            async move {
+                let version_code = match app_version.as_deref().map(VersionSpec::parse) {
+                    None => None,
+                    Some(VersionSpec::Code(code)) => match i64::try_from(code) {
+                        Ok(code) => Some(code),
+                        Err(_) => {
+                            mp_log.println(format!("Version code {} is out of range for {}. Skipping...", code, app_id)).unwrap();
+                            return;
+                        },
+                    },
+                    Some(VersionSpec::Name(name)) => {
+                        mp_log.println(format!("Specific versions can not be downloaded from Google Play by name ({}@{}), use @code:N instead. Skipping...", app_id, name)).unwrap();
+                        return;
+                    },
+                };
+                let app_string = match version_code {
+                    Some(code) => format!("{} (code {})", app_id, code),
+                    None => app_id.to_string(),
+                };
+                mp_log.suspend(|| println!("Downloading {}...", app_string));
+                if sleep_duration > 0 {
+                    sleep(TokioDuration::from_millis(sleep_duration)).await;
+                }
+                match gpa.download(&app_id, version_code, split_apk, include_dex_metadata, include_additional_files, Path::new(outpath), Some(&progress_wrapper(mp_dl1))).await {
+                    Ok(_) => mp_log.suspend(|| println!("{} downloaded successfully!", app_string)),
+                    Err(err) if matches!(err.kind(), GpapiErrorKind::FileExists) => {
+                        mp_log.println(format!("File already exists for {}. Skipping...", app_id)).unwrap();
+                    }
+                    Err(err) if matches!(err.kind(), GpapiErrorKind::DirectoryExists) => {
+                        mp_log.println(format!("Split APK directory already exists for {}. Skipping...", app_id)).unwrap();
+                    }
+                    Err(err) if matches!(err.kind(), GpapiErrorKind::InvalidApp) => {
+                        match version_code {
+                            Some(code) => mp_log.println(format!("Google Play no longer serves version code {} of {}, or the app is not available. Skipping...", code, app_id)).unwrap(),
+                            None => mp_log.println(format!("Invalid app response for {}. Skipping...", app_id)).unwrap(),
+                        }
+                    }
+                    // Google Play may accept the purchase of a version code it no longer serves,
+                    // but then not give a URL to download it from
+                    Err(err) if version_code.is_some() && matches!(err.kind(), GpapiErrorKind::Str(message) if message.contains("no download URL available")) => {
+                        mp_log.println(format!("Google Play no longer serves version code {} of {}. Skipping...", version_code.unwrap(), app_id)).unwrap();
+                    }
+                    Err(err) if matches!(err.kind(), GpapiErrorKind::PermissionDenied) => {
+                        mp_log.println(format!("Permission denied when attempting to write file for {}. Skipping...", app_id)).unwrap();
+                    }
+                    Err(_) => {
+                        mp_log.println(format!("An error has occurred attempting to download {}.  Retry #1...", app_string)).unwrap();
+                        match gpa.download(&app_id, version_code, split_apk, include_dex_metadata, include_additional_files, Path::new(outpath), Some(&progress_wrapper(mp_dl2))).await {
+                            Ok(_) => mp_log.suspend(|| println!("{} downloaded successfully!", app_string)),
+                            Err(_) => {
+                                mp_log.println(format!("An error has occurred attempting to download {}.  Retry #2...", app_string)).unwrap();
+                                match gpa.download(&app_id, version_code, split_apk, include_dex_metadata, include_additional_files, Path::new(outpath), Some(&progress_wrapper(mp_dl3))).await {
+                                    Ok(_) => mp_log.suspend(|| println!("{} downloaded successfully!", app_string)),
+                                    Err(_) => {
+                                        mp_log.println(format!("An error has occurred attempting to download {}. Skipping...", app_string)).unwrap();
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    ).buffer_unordered(parallel).collect::<Vec<()>>().await;
+}
+
+/// Logs in to Google Play as `device`, with the locale and timezone given in the options, and exits
+/// if that fails.
+async fn login(email: &str, aas_token: &str, device: &str, accept_tos: bool, options: &mut HashMap<&str, &str>) -> Gpapi {
     let mut gpa = Gpapi::new(device, email);
 
     if let Some(locale) = options.remove("locale") {
@@ -71,60 +154,7 @@ pub async fn download_apps(
             }
         }
     }
-
-    let mp = Rc::new(MultiProgress::new());
-    let gpa = Rc::new(gpa);
-    futures_util::stream::iter(
-        apps.into_iter().map(|app| {
-            let (app_id, app_version) = app;
-            let gpa = Rc::clone(&gpa);
-            let mp_dl1 = Rc::clone(&mp);
-            let mp_dl2 = Rc::clone(&mp);
-            let mp_dl3 = Rc::clone(&mp);
-            let mp_log = Rc::clone(&mp);
-
-            async move {
-                if app_version.is_none() {
-                    mp_log.suspend(|| println!("Downloading {}...", app_id));
-                    if sleep_duration > 0 {
-                        sleep(TokioDuration::from_millis(sleep_duration)).await;
-                    }
-                    match gpa.download(&app_id, None, split_apk, include_dex_metadata, include_additional_files, Path::new(outpath), Some(&progress_wrapper(mp_dl1))).await {
-                        Ok(_) => mp_log.suspend(|| println!("{} downloaded successfully!", app_id)),
-                        Err(err) if matches!(err.kind(), GpapiErrorKind::FileExists) => {
-                            mp_log.println(format!("File already exists for {}. Skipping...", app_id)).unwrap();
-                        }
-                        Err(err) if matches!(err.kind(), GpapiErrorKind::DirectoryExists) => {
-                            mp_log.println(format!("Split APK directory already exists for {}. Skipping...", app_id)).unwrap();
-                        }
-                        Err(err) if matches!(err.kind(), GpapiErrorKind::InvalidApp) => {
-                            mp_log.println(format!("Invalid app response for {}. Skipping...", app_id)).unwrap();
-                        }
-                        Err(err) if matches!(err.kind(), GpapiErrorKind::PermissionDenied) => {
-                            mp_log.println(format!("Permission denied when attempting to write file for {}. Skipping...", app_id)).unwrap();
-                        }
-                        Err(_) => {
-                            mp_log.println(format!("An error has occurred attempting to download {}.  Retry #1...", app_id)).unwrap();
-                            match gpa.download(&app_id, None, split_apk, include_dex_metadata, include_additional_files, Path::new(outpath), Some(&progress_wrapper(mp_dl2))).await {
-                                Ok(_) => mp_log.suspend(|| println!("{} downloaded successfully!", app_id)),
-                                Err(_) => {
-                                    mp_log.println(format!("An error has occurred attempting to download {}.  Retry #2...", app_id)).unwrap();
-                                    match gpa.download(&app_id, None, split_apk, include_dex_metadata, include_additional_files, Path::new(outpath), Some(&progress_wrapper(mp_dl3))).await {
-                                        Ok(_) => mp_log.suspend(|| println!("{} downloaded successfully!", app_id)),
-                                        Err(_) => {
-                                            mp_log.println(format!("An error has occurred attempting to download {}. Skipping...", app_id)).unwrap();
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                } else {
-                    mp_log.println(format!("Specific versions can not be downloaded from Google Play ({}@{}). Skipping...", app_id, app_version.unwrap())).unwrap();
-                }
-            }
-        })
-    ).buffer_unordered(parallel).collect::<Vec<()>>().await;
+    gpa
 }
 
 pub async fn request_aas_token(
@@ -145,10 +175,86 @@ pub async fn request_aas_token(
     }
 }
 
-pub fn list_versions(apps: Vec<(String, Option<String>)>) {
+/// Lists the version of each app Google Play currently serves to `device` in the requested
+/// locale, with its details.  Google Play does not list older versions.
+pub async fn list_versions(
+    apps: Vec<(String, Option<String>)>,
+    email: &str,
+    aas_token: &str,
+    accept_tos: bool,
+    mut options: HashMap<&str, &str>,
+) {
+    let output_format = match options.get("output_format") {
+        Some(val) if val.to_lowercase() == "json" => OutputFormat::Json,
+        _ => OutputFormat::Plaintext,
+    };
+    let device = options.remove("device").unwrap_or("px_9a");
+    let locale = options.get("locale").copied().unwrap_or("en_US");
+    let gpa = login(email, aas_token, device, accept_tos, &mut options).await;
+    let mut json_root: HashMap<String, Value> = HashMap::new();
+
     for app in apps {
         let (app_id, _) = app;
-        println!("Versions available for {} on Google Play:", app_id);
-        println!("| Google Play does not make old versions of apps available.");
+        if output_format.is_plaintext() {
+            println!("Versions available for {} on Google Play (device {}, locale {}):", app_id, device, locale);
+        }
+        let item = match gpa.details(&app_id).await {
+            Ok(Some(details)) => details.item,
+            _ => None,
+        };
+        let app_details = item.as_ref()
+            .and_then(|item| item.details.as_ref())
+            .and_then(|details| details.app_details.as_ref());
+        let (Some(item), Some(app_details)) = (&item, app_details) else {
+            match output_format {
+                OutputFormat::Plaintext => {
+                    eprintln!("| Invalid app response for {}. Skipping...", app_id);
+                },
+                OutputFormat::Json => {
+                    json_root.insert(app_id.to_string(), json!({"error": "Invalid app response."}));
+                },
+            }
+            continue;
+        };
+        let rating = item.aggregate_rating.as_ref().and_then(|rating| rating.star_rating);
+        match output_format {
+            OutputFormat::Plaintext => {
+                let mut details = vec![];
+                if let Some(size) = app_details.info_download_size {
+                    details.push(format_size(size.max(0) as u64));
+                }
+                if let Some(updated) = &app_details.info_updated_on {
+                    details.push(format!("updated {}", updated));
+                }
+                if let Some(developer) = &app_details.developer_name {
+                    details.push(format!("by {}", developer));
+                }
+                if let Some(rating) = rating {
+                    details.push(format!("rated {:.1}", rating));
+                }
+                println!("| {} (code {}): {}",
+                    app_details.version_string.as_deref().unwrap_or("unknown"),
+                    app_details.version_code.map_or("unknown".to_string(), |version_code| version_code.to_string()),
+                    details.join(", "));
+            },
+            OutputFormat::Json => {
+                json_root.insert(app_id.to_string(), json!({
+                    "device": device,
+                    "locale": locale,
+                    "available_versions": [{
+                        "version": app_details.version_string,
+                        "version_code": app_details.version_code,
+                        "title": item.title,
+                        "size": app_details.info_download_size,
+                        "updated": app_details.info_updated_on,
+                        "developer": app_details.developer_name,
+                        "rating": rating,
+                    }],
+                }));
+            },
+        }
+    }
+    if output_format.is_json() {
+        println!("{{\"source\":\"Google Play\",\"apps\":{}}}", json!(json_root));
     }
 }
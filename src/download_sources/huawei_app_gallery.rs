@@ -9,7 +9,7 @@ use serde_json::Value;
 use tokio_dl_stream_to_disk::{AsyncDownload, error::ErrorKind as TDSTDErrorKind};
 use tokio::time::{sleep, Duration as TokioDuration};
 
-use crate::util::progress_bar::progress_wrapper;
+use crate::util::{progress_bar::progress_wrapper, sanitize::sanitize_filename_component};
 
 fn http_headers() -> HeaderMap {
     let mut headers = HeaderMap::new();
@@ -62,7 +62,7 @@ pub async fn download_apps(
 async fn download_from_response(response: Response, app_string: String, outpath: &Path, mp: Rc<MultiProgress>) {
     let mp_log = Rc::clone(&mp);
     let mp = Rc::clone(&mp);
-    let fname = format!("{}.apk", app_string);
+    let fname = format!("{}.apk", sanitize_filename_component(&app_string));
     match response.status() {
         reqwest::StatusCode::OK => {
             let body = response.text().await.unwrap();
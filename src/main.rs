@@ -130,6 +130,7 @@ use std::fs::{self, File};
 use std::io::{self, Write, Read};
 use std::path::{Path, PathBuf};
 
+use clap::ArgMatches;
 use configparser::ini::Ini;
 
 mod cli;
@@ -138,6 +139,7 @@ use cli::DownloadSource;
 mod config;
 mod consts;
 mod util;
+use util::sanitize;
 
 mod download_sources;
 use download_sources::google_play;
@@ -146,33 +148,29 @@ use download_sources::apkpure;
 use download_sources::huawei_app_gallery;
 
 type CSVList = Vec<(String, Option<String>)>;
-fn fetch_csv_list(csv: &str, field: usize, version_field: Option<usize>) -> Result<CSVList, Box<dyn Error>> {
-    Ok(parse_csv_text(fs::read_to_string(csv)?, field, version_field))
+fn fetch_csv_list(csv: &str, field: usize, version_field: Option<usize>, version_code_field: Option<usize>) -> Result<CSVList, Box<dyn Error>> {
+    Ok(parse_csv_text(fs::read_to_string(csv)?, field, version_field, version_code_field))
 }
 
-fn parse_csv_text(text: String, field: usize, version_field: Option<usize>) -> Vec<(String, Option<String>)> {
+/// Version codes are passed on in the `code:N` form that can also be given after the `@` in an
+/// app ID.
+fn parse_csv_text(text: String, field: usize, version_field: Option<usize>, version_code_field: Option<usize>) -> Vec<(String, Option<String>)> {
     let field = field - 1;
-    let version_field = version_field.map(|version_field| version_field - 1);
+    let column = |entry_vec: &[&str], field: Option<usize>| {
+        field.and_then(|field| entry_vec.get(field - 1))
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+    };
     text.split('\n')
         .filter_map(|l| {
             let entry = l.trim();
-            let mut entry_vec = entry.split(',').collect::<Vec<&str>>();
+            let entry_vec = entry.split(',').collect::<Vec<&str>>();
             if entry_vec.len() > field && !(entry_vec.len() == 1 && entry_vec[0].is_empty()) {
-                match version_field {
-                    Some(mut version_field) if entry_vec.len() > version_field => {
-                        if version_field > field {
-                            version_field -= 1;
-                        }
-                        let app_id = String::from(entry_vec.remove(field));
-                        let app_version = String::from(entry_vec.remove(version_field));
-                        if !app_version.is_empty() {
-                            Some((app_id, Some(app_version)))
-                        } else {
-                            Some((app_id, None))
-                        }
-                    },
-                    _ => Some((String::from(entry_vec.remove(field)), None)),
-                }
+                let app_version = column(&entry_vec, version_code_field)
+                    .map(|version_code| format!("code:{}", version_code))
+                    .or_else(|| column(&entry_vec, version_field));
+                Some((String::from(entry_vec[field]), app_version))
             } else {
                 None
             }
@@ -197,6 +195,42 @@ fn load_config(ini_file: Option<PathBuf>) -> Result<Ini, Box<dyn Error>> {
     Ok(conf)
 }
 
+/// The Google Play email and AAS token, taken from the command line, then the ini file, and
+/// otherwise prompted for.
+fn google_play_credentials(matches: &ArgMatches, ini_file: Option<PathBuf>) -> (String, String) {
+    let mut email = matches.get_one::<String>("google_email").map(|v| v.to_string());
+    let mut aas_token = matches.get_one::<String>("google_aas_token").map(|v| v.to_string());
+
+    if email.is_none() || aas_token.is_none() {
+        if let Ok(conf) = load_config(ini_file) {
+            if email.is_none() {
+                email = conf.get("google", "email");
+            }
+            if aas_token.is_none() {
+                aas_token = conf.get("google", "aas_token");
+            }
+        }
+    }
+
+    if email.is_none() {
+        let mut prompt_email = String::new();
+        print!("Email: ");
+        io::stdout().flush().unwrap();
+        io::stdin().read_line(&mut prompt_email).unwrap();
+        email = Some(prompt_email.trim().to_string());
+    }
+
+    if aas_token.is_none() {
+        let mut prompt_aas_token = String::new();
+        print!("AAS Token: ");
+        io::stdout().flush().unwrap();
+        io::stdin().read_line(&mut prompt_aas_token).unwrap();
+        aas_token = Some(prompt_aas_token.trim().to_string());
+    }
+
+    (email.unwrap(), aas_token.unwrap())
+}
+
 #[tokio::main]
 async fn main() {
     let usage = {
@@ -205,7 +239,7 @@ async fn main() {
     let matches = cli::app().get_matches();
 
     let mut download_source = *matches.get_one::<DownloadSource>("download_source").unwrap();
-    let options: HashMap<&str, &str> = match matches.get_one::<String>("options") {
+    let mut options: HashMap<&str, &str> = match matches.get_one::<String>("options") {
         Some(options) => {
             let mut options_map = HashMap::new();
             for option in options.split(",") {
@@ -213,19 +247,54 @@ async fn main() {
                     Some((key, value)) => {
                         options_map.insert(key, value);
                     },
-                    None => {}
+                    None if option.is_empty() => {},
+                    None => {
+                        println!("Invalid option \"{}\", options must be given as key=value and separated by commas. Lists within an option, such as arch, are separated by semicolons. Exiting.", option);
+                        std::process::exit(1);
+                    },
                 }
             }
             options_map
         },
         None => HashMap::new()
     };
+    if matches.get_flag("offline") {
+        options.insert("offline", "true");
+    }
+    if let Some(max_index_age) = matches.get_one::<String>("max_index_age") {
+        options.insert("max_index_age", max_index_age);
+    }
+
+    let ini_file = matches.get_one::<String>("ini").map(|ini_file| {
+        match fs::canonicalize(ini_file) {
+            Ok(ini_file) if Path::new(&ini_file).is_file() => {
+                ini_file
+            },
+            _ => {
+                println!("{}\n\nSpecified ini is not a valid file", usage);
+                std::process::exit(1);
+            },
+        }
+    });
+    let apkpure_device = || {
+        match apkpure::DeviceProfile::from_options(&options, load_config(ini_file.clone()).ok().as_ref()) {
+            Ok(device) => device,
+            Err(err) => {
+                println!("{}. Exiting.", err);
+                std::process::exit(1);
+            },
+        }
+    };
 
     let oauth_token = matches.get_one::<String>("google_oauth_token").map(|v| v.to_string());
     if oauth_token.is_some() {
         download_source = DownloadSource::GooglePlay;
     }
-    let list: Vec<(String, Option<String>)> = if oauth_token.is_none() {
+    let search_query = matches.get_one::<String>("search");
+    let all_apps = matches.get_flag("all_apps");
+    let build_repo = matches.get_flag("build_repo");
+    let diff_index: Option<Vec<&String>> = matches.get_many::<String>("diff_index").map(|index_files| index_files.collect());
+    let list: Vec<(String, Option<String>)> = if oauth_token.is_none() && search_query.is_none() && !all_apps && !build_repo && diff_index.is_none() {
         match matches.get_one::<String>("app") {
             Some(app) => {
                 let mut app_vec: Vec<String> = app.splitn(2, '@').map(String::from).collect();
@@ -240,6 +309,7 @@ async fn main() {
                 let csv = matches.get_one::<String>("csv").unwrap();
                 let field = *matches.get_one::<usize>("field").unwrap();
                 let version_field = matches.get_one::<usize>("version_field").map(|v| *v);
+                let version_code_field = matches.get_one::<usize>("version_code_field").copied();
                 if field < 1 {
                     println!("{}\n\nApp ID field must be 1 or greater", usage);
                     std::process::exit(1);
@@ -254,7 +324,17 @@ async fn main() {
                         std::process::exit(1);
                     }
                 }
-                match fetch_csv_list(csv, field, version_field) {
+                if let Some(version_code_field) = version_code_field {
+                    if version_code_field < 1 {
+                        println!("{}\n\nVersion code field must be 1 or greater", usage);
+                        std::process::exit(1);
+                    }
+                    if field == version_code_field || version_field == Some(version_code_field) {
+                        println!("{}\n\nVersion code field must be different from the App ID and Version fields", usage);
+                        std::process::exit(1);
+                    }
+                }
+                match fetch_csv_list(csv, field, version_field, version_code_field) {
                     Ok(csv_list) => csv_list,
                     Err(err) => {
                         println!("{}\n\n{:?}", usage, err);
@@ -264,14 +344,58 @@ async fn main() {
             }
         }
     } else { Vec::new() };
+    let list: Vec<(String, Option<String>)> = list.into_iter()
+        .filter(|(app_id, app_version)| match sanitize::validate_app(app_id, app_version.as_deref()) {
+            Ok(()) => true,
+            Err(err) => {
+                println!("{}. Skipping...", err);
+                false
+            },
+        })
+        .collect();
 
-    if let Some(true) = matches.get_one::<bool>("list_versions") {
+    if let Some(index_files) = diff_index {
+        match download_source {
+            DownloadSource::FDroid => {
+                fdroid::diff_indexes(Path::new(index_files[0]), Path::new(index_files[1]), options);
+            }
+            _ => {
+                println!("{}\n\nIndex diffs are not supported for the {} download source", usage, download_source);
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(query) = search_query {
+        match download_source {
+            DownloadSource::APKPure => {
+                apkpure::search(query, apkpure_device(), options).await;
+            }
+            DownloadSource::FDroid => {
+                fdroid::search(query, options).await;
+            }
+            _ => {
+                println!("{}\n\nSearching is not supported for the {} download source", usage, download_source);
+                std::process::exit(1);
+            }
+        }
+    } else if matches.get_flag("reproducible_report") {
+        match download_source {
+            DownloadSource::FDroid => {
+                fdroid::reproducible_report(list, options).await;
+            }
+            _ => {
+                println!("{}\n\nReproducible build reports are not supported for the {} download source", usage, download_source);
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(true) = matches.get_one::<bool>("list_versions") {
         match download_source {
             DownloadSource::APKPure => {
-                apkpure::list_versions(list, options).await;
+                apkpure::list_versions(list, apkpure_device(), options).await;
             }
             DownloadSource::GooglePlay => {
-                google_play::list_versions(list);
+                let (email, aas_token) = google_play_credentials(&matches, ini_file.clone());
+                let accept_tos = matches.get_flag("google_accept_tos");
+                google_play::list_versions(list, &email, &aas_token, accept_tos, options).await;
             }
             DownloadSource::FDroid => {
                 fdroid::list_versions(list, options).await;
@@ -301,6 +425,16 @@ async fn main() {
             }
         });
 
+        if build_repo {
+            fdroid::build_repo(&outpath.unwrap(), options);
+            return;
+        }
+
+        if all_apps && download_source != DownloadSource::FDroid {
+            println!("{}\n\nDownloading all apps is not supported for the {} download source", usage, download_source);
+            std::process::exit(1);
+        }
+
         match download_source {
             DownloadSource::APKPure => {
                 apkpure::download_apps(
@@ -308,11 +442,12 @@ async fn main() {
                     parallel,
                     sleep_duration,
                     &outpath.unwrap(),
+                    apkpure_device(),
                     options,
                 ).await;
             }
             DownloadSource::GooglePlay => {
-                let mut email = matches.get_one::<String>("google_email").map(|v| v.to_string());
+                let email = matches.get_one::<String>("google_email").map(|v| v.to_string());
 
                 if email.is_some() && oauth_token.is_some() {
                     google_play::request_aas_token(
@@ -321,57 +456,15 @@ async fn main() {
                         options,
                     ).await;
                 } else {
-                    let mut aas_token = matches.get_one::<String>("google_aas_token").map(|v| v.to_string());
-                    let accept_tos = match matches.get_one::<bool>("list_versions") {
-                        Some(true) => true,
-                        _ => false,
-                    };
-
-                    let ini_file = matches.get_one::<String>("ini").map(|ini_file| {
-                        match fs::canonicalize(ini_file) {
-                            Ok(ini_file) if Path::new(&ini_file).is_file() => {
-                                ini_file
-                            },
-                            _ => {
-                                println!("{}\n\nSpecified ini is not a valid file", usage);
-                                std::process::exit(1);
-                            },
-                        }
-                    });
-
-                    if email.is_none() || aas_token.is_none() {
-                        if let Ok(conf) = load_config(ini_file) {
-                            if email.is_none() {
-                                email = conf.get("google", "email");
-                            }
-                            if aas_token.is_none() {
-                                aas_token = conf.get("google", "aas_token");
-                            }
-                        }
-                    }
-
-                    if email.is_none() {
-                        let mut prompt_email = String::new();
-                        print!("Email: ");
-                        io::stdout().flush().unwrap();
-                        io::stdin().read_line(&mut prompt_email).unwrap();
-                        email = Some(prompt_email.trim().to_string());
-                    }
-
-                    if aas_token.is_none() {
-                        let mut prompt_aas_token = String::new();
-                        print!("AAS Token: ");
-                        io::stdout().flush().unwrap();
-                        io::stdin().read_line(&mut prompt_aas_token).unwrap();
-                        aas_token = Some(prompt_aas_token.trim().to_string());
-                    }
+                    let (email, aas_token) = google_play_credentials(&matches, ini_file.clone());
+                    let accept_tos = matches.get_flag("google_accept_tos");
 
                     google_play::download_apps(
                         list,
                         parallel,
                         sleep_duration,
-                        &email.unwrap(),
-                        &aas_token.unwrap(),
+                        &email,
+                        &aas_token,
                         &outpath.unwrap(),
                         accept_tos,
                         options,
@@ -379,6 +472,14 @@ async fn main() {
                     .await;
                 }
             }
+            DownloadSource::FDroid if all_apps => {
+                fdroid::download_all_apps(
+                    parallel,
+                    sleep_duration,
+                    &outpath.unwrap(),
+                    options,
+                ).await;
+            }
             DownloadSource::FDroid => {
                 fdroid::download_apps(list,
                     parallel,
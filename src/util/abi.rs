@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+/// The ABIs assumed when none are given, most preferred first.
+pub const DEFAULT_ABIS: &[&str] = &["arm64-v8a", "armeabi-v7a", "armeabi", "x86", "x86_64"];
+
+/// Every ABI Android has supported.
+const KNOWN_ABIS: &[&str] = &["arm64-v8a", "armeabi-v7a", "armeabi", "x86", "x86_64", "mips", "mips64", "riscv64"];
+
+/// ABIs which a device with the given ABI can also run, most preferred first.
+fn compatible_abis(abi: &str) -> &'static [&'static str] {
+    match abi {
+        "arm64-v8a" => &["armeabi-v7a", "armeabi"],
+        "armeabi-v7a" => &["armeabi"],
+        "x86_64" => &["x86"],
+        "mips64" => &["mips"],
+        _ => &[],
+    }
+}
+
+/// The ABIs of the device apps are downloaded for, most preferred first, set with the `arch`
+/// option as a semicolon-separated list.  Each ABI brings in the ABIs it is compatible with, so
+/// `arch=arm64-v8a` also accepts `armeabi-v7a` and `armeabi` builds.
+pub struct AbiPreference {
+    requested: Vec<String>,
+    abis: Vec<String>,
+}
+
+impl AbiPreference {
+    pub fn from_options(options: &HashMap<&str, &str>) -> Option<Self> {
+        options.get("arch").map(|arch| Self::parse(arch))
+    }
+
+    /// Parses a list of ABIs separated by semicolons, as given to the `arch` option, or by commas,
+    /// since ini files treat a semicolon as the start of a comment.
+    pub fn parse(arch: &str) -> Self {
+        Self::new(arch.split([';', ',']))
+    }
+
+    fn new<'a>(given: impl Iterator<Item = &'a str>) -> Self {
+        let mut abis: Vec<String> = vec![];
+        let mut requested: Vec<String> = vec![];
+        for abi in given.map(str::trim).filter(|abi| !abi.is_empty()) {
+            if !requested.iter().any(|existing| existing == abi) {
+                requested.push(abi.to_string());
+            }
+        }
+        for abi in requested.iter().map(String::as_str).chain(requested.iter().flat_map(|abi| compatible_abis(abi).iter().copied())) {
+            if !abis.iter().any(|existing| existing == abi) {
+                abis.push(abi.to_string());
+            }
+        }
+        AbiPreference { requested, abis }
+    }
+
+    pub fn abis(&self) -> &[String] {
+        &self.abis
+    }
+
+    /// The ABIs as they were given, without the ones they are compatible with.
+    pub fn requested(&self) -> &[String] {
+        &self.requested
+    }
+
+    /// How well an APK with native code for `nativecode` suits the device: `Some(0)` is the best
+    /// possible match, and `None` means it cannot be installed.  APKs without native code run on
+    /// any device, so they are accepted, but ranked below every APK built for one of the ABIs.
+    pub fn rank(&self, nativecode: &[String]) -> Option<usize> {
+        if nativecode.is_empty() {
+            return Some(self.abis.len());
+        }
+        self.abis.iter().position(|abi| nativecode.contains(abi))
+    }
+
+    /// The preferred ABI an APK with native code for `nativecode` would run as, if it has any
+    /// native code the device can run.
+    pub fn best_match(&self, nativecode: &[String]) -> Option<&str> {
+        self.abis.iter().find(|abi| nativecode.contains(abi)).map(String::as_str)
+    }
+}
+
+impl Default for AbiPreference {
+    fn default() -> Self {
+        Self::new(DEFAULT_ABIS.iter().copied())
+    }
+}
+
+impl fmt::Display for AbiPreference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.abis.join(", "))
+    }
+}
+
+/// The ABIs an APK has native code for, from its `lib/<abi>/` directories, or for an XAPK, from
+/// its `config.<abi>.apk` splits.  Empty if it has no native code, and so runs on any ABI.
+pub fn abis_in_archive(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut abis: Vec<String> = vec![];
+    for name in archive.file_names() {
+        let abi = if let Some((abi, _)) = name.strip_prefix("lib/").and_then(|lib| lib.split_once('/')) {
+            Some(abi.to_string())
+        } else {
+            name.strip_prefix("config.")
+                .and_then(|split| split.strip_suffix(".apk"))
+                .and_then(|split| KNOWN_ABIS.iter().find(|abi| abi.replace('-', "_") == split))
+                .map(|abi| abi.to_string())
+        };
+        if let Some(abi) = abi.filter(|abi| !abi.is_empty() && !abis.contains(abi)) {
+            abis.push(abi);
+        }
+    }
+    abis.sort();
+    Ok(abis)
+}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, NaiveDate};
+
+/// Parses a `YYYY-MM-DD` date into milliseconds since the epoch, at the start of that day (UTC).
+pub fn parse_date_ms(date: &str) -> Option<u64> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let timestamp = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp_millis();
+    u64::try_from(timestamp).ok()
+}
+
+/// Formats milliseconds since the epoch as a `YYYY-MM-DD` date (UTC).
+pub fn format_date_ms(timestamp: u64) -> String {
+    match DateTime::from_timestamp_millis(timestamp as i64) {
+        Some(datetime) => datetime.format("%Y-%m-%d").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Parses a duration such as `90s`, `30m`, `12h` or `7d` into seconds.  A number without a unit
+/// is taken to be in seconds.
+pub fn parse_duration_secs(duration: &str) -> Result<u64, String> {
+    let duration = duration.trim();
+    let (number, multiplier) = match duration.char_indices().last() {
+        Some((i, 's')) => (&duration[..i], 1),
+        Some((i, 'm')) => (&duration[..i], 60),
+        Some((i, 'h')) => (&duration[..i], 60 * 60),
+        Some((i, 'd')) => (&duration[..i], 24 * 60 * 60),
+        _ => (duration, 1),
+    };
+    number.parse::<u64>().ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| format!("Invalid duration \"{}\", must be a number optionally followed by s, m, h or d", duration))
+}
+
+/// Formats a duration in seconds in its largest whole unit, e.g. `3 days`.
+pub fn format_duration_secs(duration: u64) -> String {
+    let (number, unit) = match duration {
+        d if d >= 24 * 60 * 60 => (d / (24 * 60 * 60), "day"),
+        d if d >= 60 * 60 => (d / (60 * 60), "hour"),
+        d if d >= 60 => (d / 60, "minute"),
+        d => (d, "second"),
+    };
+    format!("{} {}{}", number, unit, if number == 1 { "" } else { "s" })
+}
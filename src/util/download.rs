@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use futures_util::StreamExt;
+use indicatif::MultiProgress;
+use sha2::Digest;
+use tokio::io::AsyncWriteExt;
+use tokio_dl_stream_to_disk::error::{Error as TDSTDError, ErrorKind as TDSTDErrorKind};
+
+use crate::util::progress_bar::progress_wrapper;
+
+/// Downloads `url` to `fname` within `outpath`, retrying twice if the download fails, and returns
+/// the digest of what was downloaded, computed with `D`.  `description` identifies the file in
+/// messages.
+pub async fn download_with_retries<D: Digest>(url: &str, outpath: &Path, fname: &str, description: &str, mp: Rc<MultiProgress>) -> Option<Vec<u8>> {
+    let mp_log = Rc::clone(&mp);
+    for retry in 0..3 {
+        if retry > 0 {
+            mp_log.println(format!("An error has occurred attempting to download {}.  Retry #{}...", description, retry)).unwrap();
+        }
+        match download_and_return_digest::<D>(url, outpath, fname, Rc::clone(&mp)).await {
+            Ok(digest) => return Some(digest),
+            Err(err) if matches!(err.kind(), TDSTDErrorKind::FileExists) => {
+                mp_log.println(format!("File already exists for {}. Skipping...", description)).unwrap();
+                return None;
+            },
+            Err(err) if matches!(err.kind(), TDSTDErrorKind::PermissionDenied) => {
+                mp_log.println(format!("Permission denied when attempting to write file for {}. Skipping...", description)).unwrap();
+                return None;
+            },
+            Err(err) if retry == 0 && matches!(err.kind(), TDSTDErrorKind::InvalidResponse) => {
+                mp_log.println(format!("Invalid response for {}. Skipping...", description)).unwrap();
+                return None;
+            },
+            Err(_) => {},
+        }
+    }
+    mp_log.println(format!("An error has occurred attempting to download {}. Skipping...", description)).unwrap();
+    None
+}
+
+/// Streams `url` to `fname` within `outpath` as `AsyncDownload` does, hashing it with `D` on the
+/// way, since the sources list different digests.  A partial download is removed.
+async fn download_and_return_digest<D: Digest>(url: &str, outpath: &Path, fname: &str, mp: Rc<MultiProgress>) -> Result<Vec<u8>, TDSTDError> {
+    let file = outpath.join(fname);
+    if file.is_file() {
+        return Err(TDSTDError::new(TDSTDErrorKind::FileExists));
+    }
+    if !outpath.is_dir() {
+        return Err(TDSTDError::new(TDSTDErrorKind::DirectoryMissing));
+    }
+    let response = reqwest::get(url).await
+        .and_then(|response| response.error_for_status())
+        .map_err(|_| TDSTDError::new(TDSTDErrorKind::InvalidResponse))?;
+    let cb = response.content_length().map(|length| progress_wrapper(mp)(fname.to_string(), length));
+
+    let mut dest = tokio::fs::File::create(&file).await?;
+    let mut stream = response.bytes_stream();
+    let mut hasher = D::new();
+    let mut num_bytes_total = 0;
+    while let Some(chunk) = stream.next().await {
+        let written = match chunk {
+            Ok(chunk) => {
+                hasher.update(&chunk);
+                num_bytes_total += chunk.len() as u64;
+                dest.write_all(&chunk).await.map_err(TDSTDError::from)
+            },
+            Err(err) => Err(TDSTDError::new(TDSTDErrorKind::Other(Box::new(err)))),
+        };
+        if let Err(err) = written {
+            let _ = fs::remove_file(&file);
+            return Err(err);
+        }
+        if let Some(ref cb) = cb {
+            cb(num_bytes_total);
+        }
+    }
+    dest.flush().await?;
+    Ok(hasher.finalize().to_vec())
+}
@@ -1,4 +1,10 @@
+pub mod abi;
+pub mod date;
+pub mod download;
 pub mod progress_bar;
+pub mod quarantine;
+pub mod sanitize;
+pub mod version;
 
 #[derive(Clone)]
 pub enum OutputFormat {
@@ -6,6 +12,21 @@ pub enum OutputFormat {
     Plaintext,
 }
 
+/// Formats a size in bytes for display, e.g. `12.3 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
 impl OutputFormat {
     pub fn is_json(&self) -> bool {
         if let Self::Json = self {
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use indicatif::MultiProgress;
+
+/// What to do with a downloaded file which failed verification, set with the `on_unverified`
+/// option.
+#[derive(Clone, Copy)]
+pub enum UnverifiedPolicy {
+    /// Remove the file entirely
+    Delete,
+    /// Move the file into a `quarantine/` directory within OUTPATH, along with a file explaining
+    /// why it was quarantined
+    Quarantine,
+    /// Leave the file in OUTPATH, but with an `.unverified` suffix
+    Rename,
+    /// Leave the file as it is, with a warning
+    Keep,
+}
+
+impl UnverifiedPolicy {
+    pub fn from_options(options: &HashMap<&str, &str>) -> Result<Self, String> {
+        match options.get("on_unverified").map(|val| val.to_lowercase()) {
+            None => Ok(Self::Keep),
+            Some(val) if val == "delete" => Ok(Self::Delete),
+            Some(val) if val == "quarantine" => Ok(Self::Quarantine),
+            Some(val) if val == "rename" => Ok(Self::Rename),
+            Some(val) if val == "keep" => Ok(Self::Keep),
+            Some(val) => Err(format!("Invalid on_unverified option \"{}\", must be one of delete, quarantine, rename or keep", val)),
+        }
+    }
+
+    /// Applies the policy to `fname` within `outpath`, returning where the file ended up, if
+    /// anywhere.
+    pub fn apply(&self, outpath: &Path, fname: &str, reason: &str) -> io::Result<Option<PathBuf>> {
+        let file = outpath.join(fname);
+        match self {
+            Self::Delete => {
+                fs::remove_file(file)?;
+                Ok(None)
+            },
+            Self::Quarantine => {
+                let quarantine_dir = outpath.join("quarantine");
+                if !quarantine_dir.is_dir() {
+                    fs::create_dir(&quarantine_dir)?;
+                }
+                let quarantined_file = quarantine_dir.join(fname);
+                if let Some(parent) = quarantined_file.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(file, &quarantined_file)?;
+                fs::write(quarantine_dir.join(format!("{}.reason.txt", fname)), format!("{}\n", reason))?;
+                Ok(Some(quarantined_file))
+            },
+            Self::Rename => {
+                let unverified_file = outpath.join(format!("{}.unverified", fname));
+                fs::rename(file, &unverified_file)?;
+                Ok(Some(unverified_file))
+            },
+            Self::Keep => Ok(Some(file)),
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Delete => "deleted",
+            Self::Quarantine => "quarantined",
+            Self::Rename => "kept with an .unverified suffix",
+            Self::Keep => "kept",
+        }
+    }
+}
+
+/// A download which failed verification, and what happened to it.
+pub struct UnverifiedDownload {
+    pub app_string: String,
+    pub reason: String,
+    pub location: Option<PathBuf>,
+}
+
+/// Applies `policy` to a downloaded file which failed verification, and reports what was done.
+pub fn apply_unverified_policy(policy: UnverifiedPolicy, outpath: &Path, fname: &str, app_string: String, reason: String, mp_log: &MultiProgress) -> UnverifiedDownload {
+    let location = match policy.apply(outpath, fname, &reason) {
+        Ok(location) if matches!(policy, UnverifiedPolicy::Keep) => {
+            mp_log.suspend(|| println!("{} downloaded, but {}. Proceed with caution.", app_string, reason));
+            location
+        },
+        Ok(location) => {
+            mp_log.suspend(|| println!("{} downloaded, but {}. The file was {}.", app_string, reason, policy.description()));
+            location
+        },
+        Err(err) => {
+            mp_log.suspend(|| println!("{} downloaded, but {}. The file could not be {} ({}). Proceed with caution.", app_string, reason, policy.description(), err));
+            Some(outpath.join(fname))
+        },
+    };
+    UnverifiedDownload { app_string, reason, location }
+}
+
+/// Exit status used when every download either succeeded or was skipped, but at least one failed
+/// verification and was dealt with by a policy other than `Keep`, which leaves the exit status as it
+/// was before verification failures were reported.
+pub const UNVERIFIED_EXIT_STATUS: i32 = 2;
+
+/// Prints the summary of a run to stdout, and returns the exit status it warrants.
+pub fn print_run_report(verified: usize, unverified: &[UnverifiedDownload], policy: UnverifiedPolicy) -> i32 {
+    println!("{} file(s) downloaded and verified.", verified);
+    if unverified.is_empty() {
+        return 0;
+    }
+    println!("{} file(s) failed verification and were {}:", unverified.len(), policy.description());
+    for download in unverified {
+        match &download.location {
+            Some(location) => println!("| {}: {} ({})", download.app_string, download.reason, location.display()),
+            None => println!("| {}: {}", download.app_string, download.reason),
+        }
+    }
+    match policy {
+        UnverifiedPolicy::Keep => 0,
+        _ => UNVERIFIED_EXIT_STATUS,
+    }
+}
@@ -0,0 +1,36 @@
+/// Whether `app_id` follows the Java package name grammar Android uses: dot-separated identifiers
+/// of ASCII letters, digits and underscores, none of which start with a digit.
+pub fn is_valid_package_id(app_id: &str) -> bool {
+    !app_id.is_empty() && app_id.split('.').all(|identifier| {
+        identifier.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && identifier.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Checks an app requested with `-a` or in a CSV row before it is used in requests or filenames,
+/// returning why it was rejected if it was.
+pub fn validate_app(app_id: &str, app_version: Option<&str>) -> Result<(), String> {
+    if !is_valid_package_id(app_id) {
+        return Err(format!("\"{}\" is not a valid package ID", app_id));
+    }
+    if let Some(app_version) = app_version {
+        if matches!(app_version.trim(), "" | "." | "..") || app_version.contains(is_unsafe_in_filename) {
+            return Err(format!("\"{}\" is not a valid version for {}", app_version, app_id));
+        }
+    }
+    Ok(())
+}
+
+/// Makes `component` safe to use as, or within, a filename in OUTPATH, by replacing path
+/// separators and control characters with underscores.  A component of only dots is replaced
+/// entirely, so it cannot refer to a parent directory.
+pub fn sanitize_filename_component(component: &str) -> String {
+    if component.chars().all(|c| c == '.') {
+        return "_".repeat(component.len().max(1));
+    }
+    component.replace(is_unsafe_in_filename, "_")
+}
+
+fn is_unsafe_in_filename(c: char) -> bool {
+    c == '/' || c == '\\' || c.is_control()
+}
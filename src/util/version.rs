@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// A version requested after the `@` in an app ID, or in a CSV version column.
+#[derive(Clone, PartialEq)]
+pub enum VersionSpec {
+    /// A version name, such as `1.2.3`
+    Name(String),
+    /// A version code, given as `code:123456`
+    Code(u64),
+}
+
+impl VersionSpec {
+    /// Anything which is not `code:` followed by a number is taken to be a version name.
+    pub fn parse(version: &str) -> Self {
+        match version.strip_prefix("code:").map(|code| code.trim().parse()) {
+            Some(Ok(code)) => Self::Code(code),
+            _ => Self::Name(version.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for VersionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "{}", name),
+            Self::Code(code) => write!(f, "code:{}", code),
+        }
+    }
+}
+
+/// How many versions of an app to download when no version is specified, set with the `versions`
+/// option.
+#[derive(Clone, Copy)]
+pub enum VersionCount {
+    /// The given number of versions, newest first
+    Latest(usize),
+    All,
+}
+
+impl VersionCount {
+    pub fn from_options(options: &HashMap<&str, &str>) -> Result<Self, String> {
+        match options.get("versions").map(|val| val.to_lowercase()) {
+            None => Ok(Self::Latest(1)),
+            Some(val) if val == "latest" => Ok(Self::Latest(1)),
+            Some(val) if val == "all" => Ok(Self::All),
+            Some(val) => match val.parse() {
+                Ok(count) if count > 0 => Ok(Self::Latest(count)),
+                _ => Err(format!("Invalid versions option \"{}\", must be latest, all or a number greater than 0", val)),
+            },
+        }
+    }
+
+    /// Parses a selector requested in place of a version: `all`, or `latest:N` for the newest N
+    /// versions.
+    pub fn parse_selector(version: &str) -> Option<Self> {
+        match version.to_lowercase().as_str() {
+            "all" => Some(Self::All),
+            version => match version.strip_prefix("latest:").map(|count| count.trim().parse()) {
+                Some(Ok(count)) if count > 0 => Some(Self::Latest(count)),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn take(&self) -> usize {
+        match self {
+            Self::Latest(count) => *count,
+            Self::All => usize::MAX,
+        }
+    }
+}
@@ -0,0 +1,109 @@
+"""Writes a compiled AndroidManifest.xml, in Android's binary XML format, for the fixtures beside it.
+
+    python3 make_manifest.py OUTPUT [utf8] [obfuscated]
+
+The manifest is for `org.example.app` version 1.2 (version code 42), with a minimum SDK of 21, a
+target SDK of 34, and the INTERNET and CAMERA permissions:
+
+    <manifest xmlns:android="http://schemas.android.com/apk/res/android"
+              package="org.example.app" android:versionCode="42" android:versionName="1.2">
+        <uses-sdk android:minSdkVersion="21" android:targetSdkVersion="34" />
+        <uses-permission android:name="android.permission.INTERNET" />
+        <uses-permission android:name="android.permission.CAMERA" />
+    </manifest>
+
+Its string pool is UTF-16, as aapt writes it, unless `utf8` is given, as aapt2 writes it.  With
+`obfuscated`, the names of the android: attributes are blanked, as some obfuscators do, so they can
+only be told apart by their resource IDs.
+"""
+import struct
+import sys
+
+ANDROID_NS = 'http://schemas.android.com/apk/res/android'
+NO_INDEX = 0xffffffff
+TYPE_STRING = 0x03
+TYPE_INT_DEC = 0x10
+
+# Attribute names with resource IDs come first in the string pool, in the order of the resource map
+RESOURCE_IDS = [
+    ('versionCode', 0x0101021b),
+    ('versionName', 0x0101021c),
+    ('minSdkVersion', 0x0101020c),
+    ('targetSdkVersion', 0x01010270),
+    ('name', 0x01010003),
+]
+
+
+def chunk(chunk_type, header, body=b''):
+    header_size = 8 + len(header)
+    return struct.pack('<HHI', chunk_type, header_size, header_size + len(body)) + header + body
+
+
+def string_pool(strings, utf8):
+    offsets = b''
+    data = b''
+    for string in strings:
+        offsets += struct.pack('<I', len(data))
+        if utf8:
+            encoded = string.encode('utf-8')
+            data += bytes([len(string), len(encoded)]) + encoded + b'\0'
+        else:
+            data += struct.pack('<H', len(string)) + string.encode('utf-16-le') + b'\0\0'
+    data += b'\0' * (-len(data) % 4)
+    header = struct.pack('<IIIII', len(strings), 0, 1 << 8 if utf8 else 0, 28 + len(offsets), 0)
+    return chunk(0x0001, header, offsets + data)
+
+
+def main():
+    output = sys.argv[1]
+    utf8 = 'utf8' in sys.argv[2:]
+    obfuscated = 'obfuscated' in sys.argv[2:]
+    strings = ['' if obfuscated else name for name, _ in RESOURCE_IDS]
+    # Blanked names still take separate entries, since the resource map is indexed by them
+    for string in ['android', ANDROID_NS, 'package', 'manifest', 'uses-sdk', 'uses-permission',
+                   'org.example.app', '1.2', 'android.permission.INTERNET', 'android.permission.CAMERA']:
+        strings.append(string)
+    index = {string: i for i, string in reversed(list(enumerate(strings)))}
+    attribute_name = {name: i for i, (name, _) in enumerate(RESOURCE_IDS)}
+
+    def node_header():
+        return struct.pack('<II', 1, NO_INDEX)
+
+    def start_element(name, attributes):
+        body = b''
+        for namespace, attribute, value in attributes:
+            if isinstance(value, int):
+                raw, data_type, data = NO_INDEX, TYPE_INT_DEC, value
+            else:
+                raw, data_type, data = index[value], TYPE_STRING, index[value]
+            body += struct.pack('<IIIHBBI', namespace, attribute, raw, 8, 0, data_type, data)
+        ext = struct.pack('<IIHHHHHH', NO_INDEX, index[name], 20, 20, len(attributes), 0, 0, 0)
+        return chunk(0x0102, node_header(), ext + body)
+
+    def end_element(name):
+        return chunk(0x0103, node_header(), struct.pack('<II', NO_INDEX, index[name]))
+
+    ns = index[ANDROID_NS]
+    namespace = struct.pack('<II', index['android'], ns)
+    nodes = (chunk(0x0100, node_header(), namespace)
+             + start_element('manifest', [
+                 (ns, attribute_name['versionCode'], 42),
+                 (ns, attribute_name['versionName'], '1.2'),
+                 (NO_INDEX, index['package'], 'org.example.app'),
+             ])
+             + start_element('uses-sdk', [
+                 (ns, attribute_name['minSdkVersion'], 21),
+                 (ns, attribute_name['targetSdkVersion'], 34),
+             ])
+             + end_element('uses-sdk'))
+    for permission in ['android.permission.INTERNET', 'android.permission.CAMERA']:
+        nodes += (start_element('uses-permission', [(ns, attribute_name['name'], permission)])
+                  + end_element('uses-permission'))
+    nodes += end_element('manifest') + chunk(0x0101, node_header(), namespace)
+
+    resource_map = chunk(0x0180, b'', b''.join(struct.pack('<I', resource_id) for _, resource_id in RESOURCE_IDS))
+    open(output, 'wb').write(chunk(0x0003, b'', string_pool(strings, utf8) + resource_map + nodes))
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,88 @@
+"""Signs an unsigned APK with APK Signature Scheme v2 and v3 blocks, for the fixtures beside it.
+
+    python3 sign_v2_v3.py UNSIGNED SIGNED [v2=KIND] [v3=KIND]
+
+KIND is rsa (RSASSA-PKCS1-v1_5 with SHA2-256), ec (ECDSA P-256 with SHA2-256) or dsa (DSA with
+SHA2-256).  Each block is signed with a newly generated key, and the sha256 fingerprint of its
+certificate is printed.
+"""
+import datetime
+import hashlib
+import struct
+import sys
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import dsa, ec, padding, rsa
+from cryptography.x509.oid import NameOID
+
+ALGORITHMS = {'rsa': 0x0103, 'ec': 0x0201, 'dsa': 0x0301}
+BLOCK_IDS = {'v2': 0x7109871a, 'v3': 0xf05368c0}
+
+
+def length_prefixed(data):
+    return struct.pack('<I', len(data)) + data
+
+
+def chunked_sha256(sections):
+    digests = []
+    for section in sections:
+        for i in range(0, len(section), 1 << 20):
+            chunk = section[i:i + (1 << 20)]
+            digests.append(hashlib.sha256(b'\xa5' + struct.pack('<I', len(chunk)) + chunk).digest())
+    return hashlib.sha256(b'\x5a' + struct.pack('<I', len(digests)) + b''.join(digests)).digest()
+
+
+def signers(scheme, kind, digest):
+    key = {
+        'rsa': lambda: rsa.generate_private_key(65537, 2048),
+        'ec': lambda: ec.generate_private_key(ec.SECP256R1()),
+        'dsa': lambda: dsa.generate_private_key(2048),
+    }[kind]()
+    name = x509.Name([x509.NameAttribute(NameOID.COMMON_NAME, '%s %s' % (scheme, kind))])
+    now = datetime.datetime.now(datetime.timezone.utc)
+    cert = (x509.CertificateBuilder().subject_name(name).issuer_name(name)
+            .public_key(key.public_key()).serial_number(1)
+            .not_valid_before(now).not_valid_after(now + datetime.timedelta(days=3650))
+            .sign(key, hashes.SHA256()))
+    cert_der = cert.public_bytes(serialization.Encoding.DER)
+    algorithm = ALGORITHMS[kind]
+    # v3 signers carry the range of SDK levels they apply to, both in and after the signed data
+    sdk_range = struct.pack('<II', 24, 0x7fffffff) if scheme == 'v3' else b''
+    signed_data = (length_prefixed(length_prefixed(struct.pack('<I', algorithm) + length_prefixed(digest)))
+                   + length_prefixed(length_prefixed(cert_der)) + sdk_range + length_prefixed(b''))
+    if kind == 'rsa':
+        signature = key.sign(signed_data, padding.PKCS1v15(), hashes.SHA256())
+    elif kind == 'ec':
+        signature = key.sign(signed_data, ec.ECDSA(hashes.SHA256()))
+    else:
+        signature = key.sign(signed_data, hashes.SHA256())
+    public_key = key.public_key().public_bytes(serialization.Encoding.DER, serialization.PublicFormat.SubjectPublicKeyInfo)
+    print('%s %s %s' % (scheme, kind, hashlib.sha256(cert_der).hexdigest()))
+    signer = (length_prefixed(signed_data) + sdk_range
+              + length_prefixed(length_prefixed(struct.pack('<I', algorithm) + length_prefixed(signature)))
+              + length_prefixed(public_key))
+    return length_prefixed(length_prefixed(signer))
+
+
+def main():
+    unsigned, signed = sys.argv[1:3]
+    schemes = dict(arg.split('=') for arg in sys.argv[3:])
+    data = open(unsigned, 'rb').read()
+    eocd_offset = data.rfind(b'PK\x05\x06')
+    cd_offset = struct.unpack('<I', data[eocd_offset + 16:eocd_offset + 20])[0]
+    entries, cd, eocd = data[:cd_offset], data[cd_offset:eocd_offset], bytearray(data[eocd_offset:])
+    digest = chunked_sha256([entries, cd, bytes(eocd)])
+    pairs = b''
+    for scheme in ('v3', 'v2'):
+        if scheme in schemes:
+            value = signers(scheme, schemes[scheme], digest)
+            pairs += struct.pack('<QI', len(value) + 4, BLOCK_IDS[scheme]) + value
+    size = len(pairs) + 8 + 16
+    block = struct.pack('<Q', size) + pairs + struct.pack('<Q', size) + b'APK Sig Block 42'
+    eocd[16:20] = struct.pack('<I', len(entries) + len(block))
+    open(signed, 'wb').write(entries + block + cd + bytes(eocd))
+
+
+if __name__ == '__main__':
+    main()